@@ -1,209 +1,768 @@
-use super::types::{Res, Error, PageId};
-use super::page::Page;
-use super::diskmgr::DiskMgr;
-
+pub mod replacer;
+pub mod lru_replacer;
+pub mod clock_replacer;
+pub mod lru_k_replacer;
+pub mod two_q_replacer;
+pub mod page_guard;
+pub mod background_writer;
+pub mod buffer_ring;
+pub mod read_ahead;
+pub mod stats;
+mod free_pages;
+
+pub use replacer::*;
+pub use lru_replacer::*;
+pub use clock_replacer::*;
+pub use lru_k_replacer::*;
+pub use two_q_replacer::*;
+pub use background_writer::*;
+pub use buffer_ring::*;
+pub use read_ahead::*;
+pub use stats::*;
+use free_pages::FreePages;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use super::types::{Res, Error, PageId, EntryNo};
+use super::page::{Page, PAGE_BYTE};
+use super::diskmgr::{PageStore, DiskMgr, MemPageStore, FaultPageStore, FaultConfig};
+
+// buffer frame. page is protected by shared/exclusive latch.
+// latch is taken only by threads which pinned the frame.
 pub struct Frame {
-    page_id: PageId,
-    pin_count: usize,
-    dirty: bool,
-    page: Page
+    latch: RwLock<Page>,
+    dirty: AtomicBool,
+    owner: AtomicUsize, // entry number of heap file. NO_OWNER if unknown
 }
 
+const NO_OWNER: usize = usize::MAX;
+
 impl Frame {
-    pub fn new(page_id: PageId, page: Page) -> Self {
+    pub fn new(page: Page) -> Self {
         Self {
-            page_id,
-            pin_count: 0,
-            dirty: false,
-            page,
+            latch: RwLock::new(page),
+            dirty: AtomicBool::new(false),
+            owner: AtomicUsize::new(NO_OWNER),
         }
     }
 
-    pub fn get_page_id(&self) -> PageId {
-        self.page_id
+    pub fn get_owner(&self) -> Option<EntryNo> {
+        match self.owner.load(Ordering::Relaxed) {
+            NO_OWNER => None,
+            raw => Some(EntryNo::from_raw(raw)),
+        }
     }
 
-    pub fn get_pin_count(&self) -> usize {
-        self.pin_count
+    pub fn set_owner(&self, owner: Option<EntryNo>) {
+        let value = owner.map_or(NO_OWNER, |entry_no| entry_no.to_raw());
+        self.owner.store(value, Ordering::Relaxed);
     }
 
     pub fn get_dirty(&self) -> bool {
-        self.dirty
+        self.dirty.load(Ordering::Acquire)
     }
 
-    // pub fn get_page(&self) -> &Page {
-    //     &self.page
-    // }
-
-    // pub fn get_page_mut(&mut self) -> &mut Page {
-    //     &mut self.page
-    // }
-
-    // pub fn set_page(&mut self, page: Page) {
-    //     self.page = page;
-    // }
-
-    pub fn set_dirty(&mut self, dirty: bool) {
-        self.dirty = dirty;
+    pub fn set_dirty(&self, dirty: bool) {
+        self.dirty.store(dirty, Ordering::Release);
     }
+}
 
-    pub fn pin(&mut self) {
-        self.pin_count += 1;
-    }
+// mapping from page to frame. the lock is held only for bookkeeping, never
+// during i/o or while page is latched by caller.
+struct PoolState {
+    page_table: HashMap<PageId, usize>, // page_id -> index of frames
+    pin_counts: Vec<usize>,
+    free_frames: Vec<usize>,
+    replacer: Box<dyn Replacer>,
+    num_evictions: u64,
+    // dirty victims out of page table whose write back is in progress
+    writing_back: HashSet<PageId>,
+}
 
-    pub fn unpin(&mut self) {
-        self.pin_count -= 1;
+impl PoolState {
+    fn pin_resident(&mut self, page_id: PageId) -> Option<usize> {
+        let idx = self.pin_without_access(page_id)?;
+        self.replacer.record_access(page_id);
+        Some(idx)
     }
 
+    // pin for flushing. replacement order is kept.
+    fn pin_without_access(&mut self, page_id: PageId) -> Option<usize> {
+        let idx = *self.page_table.get(&page_id)?;
+        self.pin_counts[idx] += 1;
+        self.replacer.set_evictable(page_id, false);
+        Some(idx)
+    }
 }
 
+// freed pages kept for reuse. the rest are deallocated on checkpoint.
+const MAX_REUSABLE_PAGES: usize = 64;
+// pages allocated from store at once
+const ALLOC_BATCH: usize = 16;
+
+// lock order: state -> frame latch -> store
 pub struct BufMgr {
-    buf_pool: Vec<Frame>,
-    max_bufsize: usize,
-    diskmgr: DiskMgr,
+    frames: Vec<Frame>,
+    state: Mutex<PoolState>,
+    store: Mutex<Box<dyn PageStore>>,
+    free_pages: Mutex<FreePages>,
+    page_byte: usize,
+    stats: StatCounters,
 }
 
 impl BufMgr {
-    pub fn new(max_bufsize: usize, diskmgr: DiskMgr) -> Self {
-        let buf_pool = Vec::new();
-        // for _ in 0..max_bufsize {
-        //     buf_pool.push(Frame::new());
-        // }
+    pub fn new(max_bufsize: usize, store: impl PageStore + 'static) -> Self {
+        Self::with_replacer(max_bufsize, store, ReplacerKind::Lru)
+    }
+
+    pub fn with_replacer(max_bufsize: usize, store: impl PageStore + 'static, kind: ReplacerKind) -> Self {
+        let page_byte = store.get_page_byte();
+        let frames = (0..max_bufsize).map(|_| Frame::new(Page::new(page_byte))).collect();
+        let state = PoolState {
+            page_table: HashMap::new(),
+            pin_counts: vec![0; max_bufsize],
+            free_frames: (0..max_bufsize).rev().collect(),
+            replacer: kind.build(max_bufsize),
+            num_evictions: 0,
+            writing_back: HashSet::new(),
+        };
         Self {
-            buf_pool,
-            max_bufsize,
-            diskmgr,
+            frames,
+            state: Mutex::new(state),
+            store: Mutex::new(Box::new(store)),
+            free_pages: Mutex::new(FreePages::default()),
+            page_byte,
+            stats: StatCounters::default(),
         }
     }
 
-    // fn get_frame_mut(&mut self, page_id: PageId) -> Res<&mut Frame> {
-    //     for frame in self.buf_pool.iter_mut() {
-    //         if frame.get_page_id() == page_id {
-    //             return Ok(frame);
-    //         }
-    //     }
-    //     Err(Error::InvalidArg{ msg: format!("page_id {} is not found", page_id)})
-    // }
+    // pages loaded with ring are placed in frames owned by the ring
+    fn pin_frame(&self, page_id: PageId, ring: Option<&BufferRing>) -> Res<usize> {
+        loop {
+            let (idx, victim, num_evictions) = {
+                let mut state = self.state.lock().unwrap();
+                if let Some(idx) = state.pin_resident(page_id) {
+                    StatCounters::incr(&self.stats.hits);
+                    return Ok(idx);
+                }
+                if state.writing_back.contains(&page_id) {
+                    // evicted by other thread. page is read after write back ends.
+                    drop(state);
+                    std::thread::yield_now();
+                    continue;
+                }
+                let (idx, victim) = self.take_frame(&mut state, ring)?;
+                (idx, victim, state.num_evictions)
+            };
 
-    fn get_frame_index(&self, page_id: PageId) -> Option<usize> {
-        for (i, frame) in self.buf_pool.iter().enumerate() {
-            if frame.get_page_id() == page_id {
-                return Some(i);
+            // other threads can use buffer pool during write back and read
+            if let Some(victim) = victim {
+                self.write_back_victim(victim, idx)?;
+            }
+            let page = match self.read_page(page_id) {
+                Ok(page) => page,
+                Err(e) => {
+                    self.state.lock().unwrap().free_frames.push(idx);
+                    return Err(e);
+                }
+            };
+
+            let mut state = self.state.lock().unwrap();
+            if let Some(resident) = state.pin_resident(page_id) {
+                // loaded by other thread
+                state.free_frames.push(idx);
+                StatCounters::incr(&self.stats.hits);
+                return Ok(resident);
+            }
+            if state.num_evictions != num_evictions + victim.is_some() as u64 {
+                // page may have been loaded, modified and written back after the read
+                state.free_frames.push(idx);
+                continue;
+            }
+            if let Some(ring) = ring {
+                ring.push(idx, page_id);
             }
+            // whole page is replaced, so latch poisoned by former page is recovered
+            *self.frames[idx].latch.write().unwrap_or_else(|e| e.into_inner()) = page;
+            self.frames[idx].latch.clear_poison();
+            self.frames[idx].set_dirty(false);
+            self.frames[idx].set_owner(None);
+            state.page_table.insert(page_id, idx);
+            state.pin_resident(page_id);
+            StatCounters::incr(&self.stats.misses);
+            return Ok(idx);
         }
-        None
     }
 
-    pub fn pin_page(&mut self, page_id: PageId) -> Res<&mut Page> {
-        match self.get_frame_index(page_id) {
-            Some(idx) => {
-                let frame = self.buf_pool.get_mut(idx).unwrap();
-                frame.pin();
-                Ok(&mut frame.page)
-            },
-            None if self.buf_pool.len() < self.max_bufsize => {
-                let page = self.diskmgr.read_page(page_id)?;
-                let mut frame = Frame::new(page_id, page);
-                frame.pin();
-                self.buf_pool.push(frame);
-                Ok(&mut self.buf_pool.last_mut().unwrap().page)
-            }
-            None => panic!("not implemented for buf_pool full"),
-        }
-    }
-
-    // pub fn pin_two_pages(&mut self, pid0: PageId, pid1: PageId) -> Res<[&mut Page; 2]> {
-    //     let i0 = self.get_frame_index(pid0);
-    //     let i1 = self.get_frame_index(pid1);
-    //     match (i0, i1) {
-    //         (Some(i0), Some(i1)) => {
-    //             let ptr = self.buf_pool.as_mut_ptr();
-    //             let frames = unsafe {
-    //                 [&mut *ptr.add(i0), &mut *ptr.add(i1)]
-    //             };
-    //             for f in frames {
-    //                 f.pin();
-    //             }
-    //             let pages = [&mut frames[0].page, &mut frames[1].page];
-    //             Ok(pages)
-    //         },        
-    //         _ => panic!("one of pages is not found")
-    //     }
-    // }
-
-    pub fn unpin_page(&mut self, page_id: PageId) -> Res<()> {
-        match self.get_frame_index(page_id) {
+    fn read_page(&self, page_id: PageId) -> Res<Page> {
+        if self.free_pages.lock().unwrap().contains(page_id) {
+            return Err(Error::InvalidArg { msg: format!("page is not in use. page_id={}", page_id) });
+        }
+        self.store.lock().unwrap().read_page(page_id)
+    }
+
+    fn unpin_frame(&self, page_id: PageId) -> Res<()> {
+        let mut state = self.state.lock().unwrap();
+        match state.page_table.get(&page_id) {
             Some(idx) => {
-                let frame = self.buf_pool.get_mut(idx).unwrap();
-                frame.unpin();
+                let idx = *idx;
+                state.pin_counts[idx] -= 1;
+                if state.pin_counts[idx] == 0 {
+                    state.replacer.set_evictable(page_id, true);
+                }
                 Ok(())
             },
-            None => Err(Error::PageNotFound { page_id: page_id, msg: "page_id not found for unpin_page".to_string() })
+            None => Err(Error::PageNotFound { page_id, msg: "page_id not found for unpin_page".to_string() })
         }
     }
 
-    pub fn create_page(&mut self) -> Res<(PageId, &mut Page)> {
-        let page_id = self.diskmgr.allocate_page()?;
-        let page = self.pin_page(page_id)?;
-        Ok((page_id, page))
+    // frame for a page to be loaded. dirty victim is returned with the frame;
+    // it is out of page table and has to be written back by the caller.
+    fn take_frame(&self, state: &mut PoolState, ring: Option<&BufferRing>) -> Res<(usize, Option<PageId>)> {
+        if let Some(idx) = state.free_frames.pop() {
+            return Ok((idx, None));
+        }
+        let victim = match ring.and_then(|ring| self.ring_victim(state, ring)) {
+            Some(victim) => {
+                state.replacer.remove(victim);
+                victim
+            }
+            None => state.replacer.evict().ok_or(Error::NoFreeFrame)?,
+        };
+        let idx = state.page_table.remove(&victim).unwrap();
+        let frame = &self.frames[idx];
+        log::trace!("BufMgr::evict: page_id={}, dirty={}", victim, frame.get_dirty());
+        if frame.get_dirty() && !frame.latch.is_poisoned() {
+            state.writing_back.insert(victim);
+            return Ok((idx, Some(victim)));
+        }
+        if frame.get_dirty() {
+            // page modified partly by panicked thread is dropped without write back
+            log::error!("BufMgr: discard page with poisoned latch. page_id={}", victim);
+            frame.set_dirty(false);
+        }
+        self.evicted(state);
+        Ok((idx, None))
     }
 
-    // pub fn is_free_page(&mut self, page_id: PageId) -> Res<bool> {
-    //     self.diskmgr.is_free_page(page_id)
-    // }
+    // the oldest frame of full ring if its page is still unpinned there
+    fn ring_victim(&self, state: &PoolState, ring: &BufferRing) -> Option<PageId> {
+        let (idx, victim) = ring.pop_if_full()?;
+        if state.page_table.get(&victim) != Some(&idx) || state.pin_counts[idx] > 0 {
+            return None;
+        }
+        Some(victim)
+    }
+
+    // called without pool lock. nobody else uses the frame, since the victim is
+    // unpinned and out of page table.
+    fn write_back_victim(&self, victim: PageId, idx: usize) -> Res<()> {
+        let frame = &self.frames[idx];
+        let res = {
+            let page = frame.latch.read().unwrap_or_else(|e| e.into_inner());
+            self.write_to_store(victim, &page)
+        };
+        let mut state = self.state.lock().unwrap();
+        state.writing_back.remove(&victim);
+        if let Err(e) = res {
+            // victim stays in buffer pool
+            state.page_table.insert(victim, idx);
+            state.replacer.record_access(victim);
+            state.replacer.set_evictable(victim, true);
+            return Err(e);
+        }
+        frame.set_dirty(false);
+        StatCounters::incr(&self.stats.dirty_writes);
+        self.evicted(&mut state);
+        Ok(())
+    }
+
+    fn evicted(&self, state: &mut PoolState) {
+        StatCounters::incr(&self.stats.evictions);
+        state.num_evictions += 1;
+    }
+
+    pub fn get_page_byte(&self) -> usize {
+        self.page_byte
+    }
+
+    pub fn get_catalog_root(&self) -> PageId {
+        self.store.lock().unwrap().get_catalog_root()
+    }
 
-    pub fn flush_page(&mut self, page_id: PageId) -> Res<()> {
-        if let Some(idx) = self.get_frame_index(page_id) {
-            let frame = self.buf_pool.get_mut(idx).unwrap();
-            if frame.get_dirty() {
-                self.diskmgr.write_page(page_id, &frame.page)?;
-                frame.set_dirty(false);
+    pub fn set_catalog_root(&self, page_id: PageId) -> Res<()> {
+        self.store.lock().unwrap().set_catalog_root(page_id)
+    }
+
+    fn write_to_store(&self, page_id: PageId, page: &Page) -> Res<()> {
+        self.store.lock().unwrap().write_page(page_id, page)
+    }
+
+    // allocation and empty contents have to reach disk before any page
+    // referring to the allocated page does, or a crash may leave the reference
+    // to a free page or to stale data. pages are allocated in batch so that one
+    // sync covers many of them.
+    fn allocate_page(&self) -> Res<PageId> {
+        let mut page_ids = Vec::with_capacity(ALLOC_BATCH);
+        let empty = Page::new(self.page_byte);
+        let mut store = self.store.lock().unwrap();
+        let res = (0..ALLOC_BATCH)
+            .try_for_each(|_| {
+                let page_id = store.allocate()?;
+                page_ids.push(page_id);
+                store.write_page(page_id, &empty)
+            })
+            .and_then(|_| store.sync());
+        if let Err(e) = res {
+            for page_id in &page_ids {
+                if let Err(e) = store.deallocate(*page_id) {
+                    log::error!("BufMgr: failed to deallocate page. page_id={} {:?}", page_id, e);
+                }
             }
-            Ok(())
-        } else {
-            Err(Error::PageNotFound { page_id: page_id, msg: "page_id not found for flush_page".to_string() })
+            return Err(e);
         }
+        drop(store);
+        // lower page ids are used first
+        page_ids.reverse();
+        let page_id = page_ids.pop().unwrap();
+        self.free_pages.lock().unwrap().add_allocated(page_ids);
+        Ok(page_id)
+    }
 
+    pub fn flush_page(&self, page_id: PageId) -> Res<()> {
+        let idx = self.state.lock().unwrap().pin_without_access(page_id)
+            .ok_or(Error::PageNotFound { page_id, msg: "page_id not found for flush_page".to_string() })?;
+        let res = self.write_back(page_id, idx);
+        self.unpin_frame(page_id)?;
+        if self.frames[idx].latch.is_poisoned() {
+            self.discard_frame(page_id, idx);
+        }
+        res
     }
 
-    pub fn free_page(&mut self, page_id: PageId) -> Res<()> {
-        match self.get_frame_index(page_id) {
-            Some(idx) => {
-                let frame = self.buf_pool.get_mut(idx).unwrap();
-                if frame.get_pin_count() > 0 {
-                    return Err(Error::InvalidArg{ msg: format!("page is pinned. pid={} pin_count={}", page_id, frame.get_pin_count())});
+    // page modified partly by panicked thread is dropped from buffer pool
+    // without write back. it is read from store again on next fetch.
+    fn discard_frame(&self, page_id: PageId, idx: usize) {
+        let mut state = self.state.lock().unwrap();
+        if state.page_table.get(&page_id) != Some(&idx) || state.pin_counts[idx] > 0 {
+            return;
+        }
+        log::error!("BufMgr: discard page with poisoned latch. page_id={}", page_id);
+        self.frames[idx].set_dirty(false);
+        state.page_table.remove(&page_id);
+        state.replacer.remove(page_id);
+        state.free_frames.push(idx);
+    }
+
+    fn write_back(&self, page_id: PageId, idx: usize) -> Res<()> {
+        let frame = &self.frames[idx];
+        // partly modified page isn't written back
+        let page = frame.latch.read().map_err(|_| Error::Corruption { page_id })?;
+        if frame.get_dirty() {
+            self.write_to_store(page_id, &page)?;
+            frame.set_dirty(false);
+            StatCounters::incr(&self.stats.dirty_writes);
+        }
+        Ok(())
+    }
+
+    // write back up to `max_pages` dirty and unpinned frames
+    pub fn write_back_dirty(&self, max_pages: usize) -> Res<usize> {
+        let page_ids: Vec<PageId> = {
+            let state = self.state.lock().unwrap();
+            state.page_table.iter()
+                .filter(|(_, idx)| state.pin_counts[**idx] == 0 && self.frames[**idx].get_dirty())
+                .map(|(page_id, _)| *page_id)
+                .take(max_pages)
+                .collect()
+        };
+        self.flush_pages(&page_ids)?;
+        Ok(page_ids.len())
+    }
+
+    // checkpoint. every dirty page is written back and store is synced.
+    // pages freed before it become reusable.
+    pub fn flush_all(&self) -> Res<()> {
+        let freed = self.free_pages.lock().unwrap().take_pending();
+        let page_ids: Vec<PageId> = self.state.lock().unwrap().page_table.keys().copied().collect();
+        let res = self.flush_pages(&page_ids).and_then(|_| {
+            self.wait_for_write_backs();
+            self.clear_pages(&freed)?;
+            self.sync_store()
+        });
+        let excess = {
+            let mut free_pages = self.free_pages.lock().unwrap();
+            if res.is_err() {
+                free_pages.restore_pending(freed);
+                return res;
+            }
+            free_pages.add_reusable(freed, MAX_REUSABLE_PAGES)
+        };
+        // deallocation reaches disk with the next sync
+        self.deallocate(&excess)
+    }
+
+    // freed pages are emptied on disk before reuse, so that a page referring to
+    // a reused one never reaches stale data of its former use.
+    fn clear_pages(&self, page_ids: &[PageId]) -> Res<()> {
+        let empty = Page::new(self.page_byte);
+        let mut store = self.store.lock().unwrap();
+        for page_id in page_ids {
+            store.write_page(*page_id, &empty)?;
+        }
+        Ok(())
+    }
+
+    fn deallocate(&self, page_ids: &[PageId]) -> Res<()> {
+        let mut store = self.store.lock().unwrap();
+        for page_id in page_ids {
+            store.deallocate(*page_id)?;
+        }
+        Ok(())
+    }
+
+    // victims evicted by other threads reach store before it is synced
+    fn wait_for_write_backs(&self) {
+        while !self.state.lock().unwrap().writing_back.is_empty() {
+            std::thread::yield_now();
+        }
+    }
+
+    // failure of one page doesn't stop write back of others. the first error
+    // is returned after every page is tried.
+    fn flush_pages(&self, page_ids: &[PageId]) -> Res<()> {
+        let mut error = None;
+        for page_id in page_ids {
+            match self.flush_page(*page_id) {
+                // evicted pages were written back
+                Ok(()) | Err(Error::PageNotFound { .. }) => {},
+                Err(e) => {
+                    log::error!("BufMgr: failed to flush page. page_id={} {:?}", page_id, e);
+                    error.get_or_insert(e);
                 }
-                self.diskmgr.deallocate_page(page_id)?;
-                self.buf_pool.remove(idx);
-            },
-            None => {
-                self.diskmgr.deallocate_page(page_id)?;
             }
         }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn sync_store(&self) -> Res<()> {
+        self.store.lock().unwrap().sync()
+    }
+
+    // page is reused after the next checkpoint, when pages which referred to
+    // it have reached disk.
+    pub fn free_page(&self, page_id: PageId) -> Res<()> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(idx) = state.page_table.get(&page_id) {
+            let idx = *idx;
+            if state.pin_counts[idx] > 0 {
+                return Err(Error::InvalidArg{ msg: format!("page is pinned. pid={} pin_count={}", page_id, state.pin_counts[idx])});
+            }
+            self.frames[idx].set_dirty(false);
+            state.page_table.remove(&page_id);
+            state.free_frames.push(idx);
+            state.replacer.remove(page_id);
+        }
+        drop(state);
+        if !self.free_pages.lock().unwrap().push(page_id) {
+            return Err(Error::InvalidArg { msg: format!("page is already freed. page_id={}", page_id) });
+        }
         Ok(())
     }
+
+    fn get_pin_count(&self, page_id: PageId) -> Option<usize> {
+        let state = self.state.lock().unwrap();
+        state.page_table.get(&page_id).map(|idx| state.pin_counts[*idx])
+    }
+
+    pub fn get_stats(&self) -> BufMgrStats {
+        self.stats.snapshot()
+    }
+
+    // resident pages ordered by frame
+    pub fn get_frames(&self) -> Vec<FrameInfo> {
+        let state = self.state.lock().unwrap();
+        let mut frames: Vec<FrameInfo> = state.page_table.iter()
+            .map(|(page_id, idx)| FrameInfo {
+                frame_no: *idx,
+                page_id: *page_id,
+                pin_count: state.pin_counts[*idx],
+                dirty: self.frames[*idx].get_dirty(),
+                owner: self.frames[*idx].get_owner(),
+            })
+            .collect();
+        frames.sort_by_key(|frame| frame.frame_no);
+        frames
+    }
+
+    fn get_num_dirty(&self) -> usize {
+        self.frames.iter().filter(|frame| frame.get_dirty()).count()
+    }
 }
 
-pub fn run_bufmgr() -> Res<()> {
-    let diskmgr = DiskMgr::open_db("nano.db").unwrap();
-    let mut bufmgr = BufMgr::new(10, diskmgr);
-    let (pid0, page0) = bufmgr.create_page().unwrap();
+impl Drop for BufMgr {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush_all() {
+            log::error!("BufMgr::drop: failed to flush pages: {:?}", e);
+            return;
+        }
+        // freed pages kept for reuse are given back to store
+        let page_ids = self.free_pages.lock().unwrap().take_all();
+        if let Err(e) = self.deallocate(&page_ids).and_then(|_| self.sync_store()) {
+            log::error!("BufMgr::drop: failed to deallocate freed pages: {:?}", e);
+        }
+    }
+}
 
-    page0.set_varchar_value(10, "written by bufmgr")?;
+pub fn run_bufmgr() -> Res<()> {
+    let bufmgr = BufMgr::new(10, MemPageStore::new(PAGE_BYTE));
+    let pid0 = {
+        let mut page0 = bufmgr.new_page()?;
+        page0.set_varchar_value(10, "written by bufmgr")?;
+        page0.get_page_id()
+    };
     bufmgr.flush_page(pid0)?;
-    bufmgr.unpin_page(pid0)?;
 
-    let page1 = bufmgr.pin_page(pid0)?;
+    let page1 = bufmgr.fetch_page_read(pid0)?;
     assert_eq!(page1.get_varchar_value(10, 17)?, "written by bufmgr");
-    bufmgr.unpin_page(pid0)?;
+    drop(page1);
+
+    let pid1 = {
+        let mut page1 = bufmgr.new_page()?;
+        page1.set_int_value(0, 1)?;
+        page1.get_page_id()
+    };
 
     bufmgr.free_page(pid0)?;
-    let e = bufmgr.pin_page(pid0);
+    let e = bufmgr.fetch_page_read(pid0);
     assert!(e.is_err());
 
-    // remove nano.db file
-    std::fs::remove_file("nano.db").unwrap();
+    // released frame is reused without moving other frames
+    bufmgr.new_page()?;
+    assert_eq!(2, bufmgr.state.lock().unwrap().page_table.len());
+    assert_eq!(1, bufmgr.fetch_page_read(pid1)?.get_int_value(0)?);
+
+    // freed page is reused after checkpoint
+    assert!(bufmgr.free_page(pid0).is_err());
+    bufmgr.flush_all()?;
+    assert_eq!(pid0, bufmgr.new_page()?.get_page_id());
+
+    Ok(())
+}
+
+pub fn run_bufmgr_page_guard() -> Res<()> {
+    let bufmgr = BufMgr::new(2, MemPageStore::new(PAGE_BYTE));
+    let pid0 = {
+        let mut page = bufmgr.new_page()?;
+        page.set_varchar_value(10, "written by guard")?;
+        page.get_page_id()
+    };
+
+    // dropped guard releases pin even if caller returns early
+    let read = |bufmgr: &BufMgr| -> Res<String> {
+        let page = bufmgr.fetch_page_read(pid0)?;
+        // out of range
+        page.get_varchar_value(page.get_size(), 1)
+    };
+    assert!(read(&bufmgr).is_err());
+    assert_eq!(Some(0), bufmgr.get_pin_count(pid0));
+
+    // page modified through guard is written back on eviction
+    for _ in 0..2 {
+        let pid = bufmgr.new_page()?.get_page_id();
+        bufmgr.fetch_page_write(pid)?.set_int_value(0, 1)?;
+    }
+    assert_eq!(None, bufmgr.get_pin_count(pid0));
+    let page = bufmgr.fetch_page_read(pid0)?;
+    assert_eq!("written by guard", page.get_varchar_value(10, 16)?);
+    drop(page);
+
+    // page latched by panicked thread can't be fetched, and its pin is released
+    let res = std::thread::scope(|s| s.spawn(|| {
+        let _page = bufmgr.fetch_page_write(pid0).unwrap();
+        panic!("panic while page is latched");
+    }).join());
+    assert!(res.is_err());
+    assert!(matches!(bufmgr.fetch_page_read(pid0), Err(Error::Corruption { .. })));
+    assert!(matches!(bufmgr.fetch_page_write(pid0), Err(Error::Corruption { .. })));
+    assert_eq!(Some(0), bufmgr.get_pin_count(pid0));
+
+    // checkpoint writes back other pages and drops the poisoned one
+    let pid1 = bufmgr.new_page()?.get_page_id();
+    assert!(matches!(bufmgr.flush_all(), Err(Error::Corruption { .. })));
+    assert_eq!(0, bufmgr.get_num_dirty());
+    assert_eq!(None, bufmgr.get_pin_count(pid0));
+    assert_eq!("written by guard", bufmgr.fetch_page_read(pid0)?.get_varchar_value(10, 16)?);
+    bufmgr.fetch_page_read(pid1)?;
+    bufmgr.flush_all()?;
+    Ok(())
+}
+
+pub fn run_bufmgr_concurrent() -> Res<()> {
+    let bufmgr = Arc::new(BufMgr::new(8, MemPageStore::new(PAGE_BYTE)));
+    let mut pids = Vec::new();
+    for _ in 0..16 {
+        pids.push(bufmgr.new_page()?.get_page_id());
+    }
+
+    // each thread increments counters on its own pages while reading others
+    let handles: Vec<_> = (0..4).map(|t| {
+        let bufmgr = bufmgr.clone();
+        let pids = pids.clone();
+        std::thread::spawn(move || -> Res<()> {
+            for round in 0..100 {
+                for (i, pid) in pids.iter().enumerate() {
+                    if i % 4 == t {
+                        let mut page = bufmgr.fetch_page_write(*pid)?;
+                        let n = page.get_int_value(0)?;
+                        page.set_int_value(0, n + 1)?;
+                    } else {
+                        let page = bufmgr.fetch_page_read(*pid)?;
+                        assert!(page.get_int_value(0)? <= 100);
+                    }
+                }
+                if round % 10 == 0 {
+                    bufmgr.flush_all()?;
+                }
+            }
+            Ok(())
+        })
+    }).collect();
+    for h in handles {
+        h.join().unwrap()?;
+    }
+
+    for pid in &pids {
+        assert_eq!(100, bufmgr.fetch_page_read(*pid)?.get_int_value(0)?);
+        assert_eq!(Some(0), bufmgr.get_pin_count(*pid));
+    }
+
+    // each miss fills a frame, even if loading the page was retried
+    let stats = bufmgr.get_stats();
+    assert!(stats.misses <= stats.evictions + 8);
+    Ok(())
+}
+
+pub fn run_bufmgr_background_writer() -> Res<()> {
+    let bufmgr = Arc::new(BufMgr::new(8, MemPageStore::new(PAGE_BYTE)));
+    for i in 0..8 {
+        bufmgr.new_page()?.set_int_value(0, i)?;
+    }
+    assert_eq!(8, bufmgr.get_num_dirty());
+
+    let config = BackgroundWriterConfig { interval: Duration::from_millis(5), max_pages: 2 };
+    let writer = BackgroundWriter::start(&bufmgr, config);
+    let start = Instant::now();
+    while bufmgr.get_num_dirty() > 0 {
+        assert!(start.elapsed() < Duration::from_secs(10));
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    writer.stop();
+
+    // writer stops by itself when buffer pool is dropped
+    let writer = BackgroundWriter::start(&bufmgr, BackgroundWriterConfig::default());
+    drop(bufmgr);
+    writer.stop();
+    Ok(())
+}
+
+pub fn run_bufmgr_shutdown() -> Res<()> {
+    let name = "nano-bufmgr-shutdown.db";
+    let pid = {
+        let bufmgr = BufMgr::new(10, DiskMgr::open_db(name)?);
+        let mut page = bufmgr.new_page()?;
+        page.set_varchar_value(10, "written before drop")?;
+        page.get_page_id()
+    };
+
+    // dirty page was written back when buffer pool was dropped
+    let mut diskmgr = DiskMgr::open_db(name)?;
+    let page = diskmgr.read_page(pid)?;
+    assert_eq!("written before drop", page.get_varchar_value(10, 19)?);
+    std::fs::remove_file(name).unwrap();
+    Ok(())
+}
 
+pub fn run_bufmgr_ring() -> Res<()> {
+    let bufmgr = BufMgr::new(8, MemPageStore::new(PAGE_BYTE));
+    let hot: Vec<PageId> = (0..4)
+        .map(|_| bufmgr.new_page().map(|page| page.get_page_id()))
+        .collect::<Res<_>>()?;
+    let scan: Vec<PageId> = (0..20)
+        .map(|_| bufmgr.new_page().map(|page| page.get_page_id()))
+        .collect::<Res<_>>()?;
+    let is_resident = |pid: &PageId| bufmgr.get_pin_count(*pid).is_some();
+
+    // large scan through a ring keeps hot pages
+    for pid in &hot {
+        bufmgr.fetch_page_read(*pid)?;
+    }
+    let ring = BufferRing::new(2);
+    for pid in &scan {
+        bufmgr.fetch_page_read_in(*pid, Some(&ring))?;
+    }
+    assert!(hot.iter().all(is_resident));
+
+    // without ring, scan flushes them
+    for pid in &scan {
+        bufmgr.fetch_page_read(*pid)?;
+    }
+    assert!(!hot.iter().any(is_resident));
+    Ok(())
+}
+
+pub fn run_bufmgr_fault() -> Res<()> {
+    let config = FaultConfig { seed: 3, write_error_rate: 1.0, ..FaultConfig::default() };
+    let store = FaultPageStore::new(MemPageStore::new(PAGE_BYTE), config);
+    let faults = store.controller();
+    faults.set_enabled(false);
+    let bufmgr = BufMgr::new(10, store);
+    let pid0 = {
+        let mut page0 = bufmgr.new_page()?;
+        page0.set_varchar_value(10, "written by bufmgr")?;
+        page0.get_page_id()
+    };
+
+    // failed flush keeps the page dirty
+    faults.set_enabled(true);
+    assert!(matches!(bufmgr.flush_page(pid0), Err(Error::IoError(_))));
+    assert!(matches!(bufmgr.flush_all(), Err(Error::IoError(_))));
+    faults.set_enabled(false);
+    bufmgr.flush_page(pid0)?;
+    bufmgr.flush_all()?;
+    Ok(())
+}
+
+pub fn run_bufmgr_eviction(kind: ReplacerKind) -> Res<()> {
+    let bufmgr = BufMgr::with_replacer(3, MemPageStore::new(PAGE_BYTE), kind);
+    let mut pids = Vec::new();
+    for i in 0..10 {
+        let mut page = bufmgr.new_page()?;
+        page.set_int_value(0, i)?;
+        pids.push(page.get_page_id());
+    }
+    assert_eq!(3, bufmgr.state.lock().unwrap().page_table.len());
+
+    // evicted pages were written back
+    for (i, pid) in pids.iter().enumerate() {
+        let page = bufmgr.fetch_page_read(*pid)?;
+        assert_eq!(i as i32, page.get_int_value(0)?);
+    }
+
+    // every frame is pinned
+    let mut guards: Vec<_> = pids[..3].iter()
+        .map(|pid| bufmgr.fetch_page_read(*pid))
+        .collect::<Res<_>>()?;
+    assert!(matches!(bufmgr.fetch_page_read(pids[3]), Err(Error::NoFreeFrame)));
+    assert!(matches!(bufmgr.new_page(), Err(Error::NoFreeFrame)));
+    guards.remove(1);
+    assert_eq!(3, bufmgr.fetch_page_read(pids[3])?.get_int_value(0)?);
     Ok(())
 }
 
@@ -215,4 +774,41 @@ mod tests {
     fn test_bufmgr() {
         run_bufmgr().unwrap();
     }
+
+    #[test]
+    fn test_bufmgr_eviction() {
+        for kind in [ReplacerKind::Lru, ReplacerKind::Clock, ReplacerKind::LruK(2), ReplacerKind::TwoQ] {
+            run_bufmgr_eviction(kind).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_bufmgr_page_guard() {
+        run_bufmgr_page_guard().unwrap();
+    }
+
+    #[test]
+    fn test_bufmgr_concurrent() {
+        run_bufmgr_concurrent().unwrap();
+    }
+
+    #[test]
+    fn test_bufmgr_background_writer() {
+        run_bufmgr_background_writer().unwrap();
+    }
+
+    #[test]
+    fn test_bufmgr_shutdown() {
+        run_bufmgr_shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_bufmgr_ring() {
+        run_bufmgr_ring().unwrap();
+    }
+
+    #[test]
+    fn test_bufmgr_fault() {
+        run_bufmgr_fault().unwrap();
+    }
 }
\ No newline at end of file
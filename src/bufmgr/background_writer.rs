@@ -0,0 +1,62 @@
+use std::sync::{Arc, Weak};
+use std::sync::mpsc::{self, Sender, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use super::BufMgr;
+
+#[derive(Clone, Copy, Debug)]
+pub struct BackgroundWriterConfig {
+    pub interval: Duration,
+    pub max_pages: usize, // pages written in each interval
+}
+
+impl Default for BackgroundWriterConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(100),
+            max_pages: 16,
+        }
+    }
+}
+
+// thread trickling dirty unpinned frames to store so that eviction rarely
+// has to write. it exits when stopped or when buffer pool is dropped.
+pub struct BackgroundWriter {
+    stop_tx: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl BackgroundWriter {
+    pub fn start(bufmgr: &Arc<BufMgr>, config: BackgroundWriterConfig) -> BackgroundWriter {
+        let bufmgr: Weak<BufMgr> = Arc::downgrade(bufmgr);
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        let handle = std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stop_rx.recv_timeout(config.interval) {
+                let Some(bufmgr) = bufmgr.upgrade() else {
+                    break;
+                };
+                if let Err(e) = bufmgr.write_back_dirty(config.max_pages) {
+                    log::warn!("BackgroundWriter: failed to write back pages: {:?}", e);
+                }
+            }
+        });
+        BackgroundWriter {
+            stop_tx: Some(stop_tx),
+            handle: Some(handle),
+        }
+    }
+
+    // waits until the thread exits
+    pub fn stop(self) {}
+}
+
+impl Drop for BackgroundWriter {
+    fn drop(&mut self) {
+        // disconnecting channel wakes up the thread
+        self.stop_tx.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::types::PageId;
+
+// small set of frames reused by one large scan. pages read by the scan
+// replace each other instead of evicting hot pages of the pool.
+pub struct BufferRing {
+    size: usize,
+    frames: Mutex<VecDeque<(usize, PageId)>>, // frame index and page loaded there
+}
+
+impl BufferRing {
+    pub fn new(size: usize) -> BufferRing {
+        BufferRing {
+            size: size.max(1),
+            frames: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub(super) fn pop_if_full(&self) -> Option<(usize, PageId)> {
+        let mut frames = self.frames.lock().unwrap();
+        if frames.len() < self.size {
+            return None;
+        }
+        frames.pop_front()
+    }
+
+    pub(super) fn push(&self, idx: usize, page_id: PageId) {
+        let mut frames = self.frames.lock().unwrap();
+        frames.push_back((idx, page_id));
+        if frames.len() > self.size {
+            frames.pop_front();
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use crate::types::PageId;
+use super::Replacer;
+
+// second chance. hand skips and clears referenced pages once.
+pub struct ClockReplacer {
+    slots: Vec<Option<ClockSlot>>,
+    slot_of: HashMap<PageId, usize>,
+    free_slots: Vec<usize>,
+    hand: usize,
+    num_evictable: usize,
+}
+
+struct ClockSlot {
+    page_id: PageId,
+    referenced: bool,
+    evictable: bool,
+}
+
+impl ClockReplacer {
+    pub fn new() -> ClockReplacer {
+        ClockReplacer {
+            slots: Vec::new(),
+            slot_of: HashMap::new(),
+            free_slots: Vec::new(),
+            hand: 0,
+            num_evictable: 0,
+        }
+    }
+
+    fn take_slot(&mut self, idx: usize) -> PageId {
+        let slot = self.slots[idx].take().unwrap();
+        if slot.evictable {
+            self.num_evictable -= 1;
+        }
+        self.slot_of.remove(&slot.page_id);
+        self.free_slots.push(idx);
+        slot.page_id
+    }
+}
+
+impl Replacer for ClockReplacer {
+    fn record_access(&mut self, page_id: PageId) {
+        if let Some(idx) = self.slot_of.get(&page_id) {
+            self.slots[*idx].as_mut().unwrap().referenced = true;
+            return;
+        }
+        let slot = Some(ClockSlot { page_id, referenced: true, evictable: false });
+        let idx = match self.free_slots.pop() {
+            Some(idx) => {
+                self.slots[idx] = slot;
+                idx
+            }
+            None => {
+                self.slots.push(slot);
+                self.slots.len() - 1
+            }
+        };
+        self.slot_of.insert(page_id, idx);
+    }
+
+    fn set_evictable(&mut self, page_id: PageId, evictable: bool) {
+        if !self.slot_of.contains_key(&page_id) {
+            self.record_access(page_id);
+        }
+        let slot = self.slots[self.slot_of[&page_id]].as_mut().unwrap();
+        if slot.evictable != evictable {
+            slot.evictable = evictable;
+            if evictable {
+                self.num_evictable += 1;
+            } else {
+                self.num_evictable -= 1;
+            }
+        }
+    }
+
+    fn evict(&mut self) -> Option<PageId> {
+        if self.num_evictable == 0 {
+            return None;
+        }
+        // every referenced bit is cleared in the first round
+        loop {
+            let idx = self.hand;
+            self.hand = (self.hand + 1) % self.slots.len();
+            if let Some(slot) = self.slots[idx].as_mut() {
+                if !slot.evictable {
+                    continue;
+                }
+                if slot.referenced {
+                    slot.referenced = false;
+                    continue;
+                }
+                return Some(self.take_slot(idx));
+            }
+        }
+    }
+
+    fn remove(&mut self, page_id: PageId) {
+        if let Some(idx) = self.slot_of.get(&page_id) {
+            self.take_slot(*idx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_replacer() {
+        let mut r = ClockReplacer::new();
+        for pid in 1..=3 {
+            r.record_access(pid);
+            r.set_evictable(pid, true);
+        }
+        // every page is referenced. first page after one round is chosen.
+        assert_eq!(Some(1), r.evict());
+        r.record_access(2);
+        r.record_access(4);
+        r.set_evictable(4, true);
+        assert_eq!(Some(3), r.evict());
+        r.set_evictable(2, false);
+        assert_eq!(Some(4), r.evict());
+        assert_eq!(None, r.evict());
+        r.remove(2);
+        assert!(r.slot_of.is_empty());
+    }
+}
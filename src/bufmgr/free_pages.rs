@@ -0,0 +1,72 @@
+use std::collections::HashSet;
+
+use crate::types::PageId;
+
+// pages allocated in store but not in use. pages released by free_page are
+// handed out again only after a checkpoint has written back the pages which
+// referred to them, so a crash can't leave a reference to a page used for
+// other data.
+#[derive(Default)]
+pub(super) struct FreePages {
+    pending: Vec<PageId>, // freed after the last checkpoint
+    reusable: Vec<PageId>, // the last pushed page is used first
+    freed: HashSet<PageId>, // pages of both lists
+}
+
+impl FreePages {
+    pub fn contains(&self, page_id: PageId) -> bool {
+        self.freed.contains(&page_id)
+    }
+
+    pub fn push(&mut self, page_id: PageId) -> bool {
+        if !self.freed.insert(page_id) {
+            return false;
+        }
+        self.pending.push(page_id);
+        true
+    }
+
+    pub fn pop_reusable(&mut self) -> Option<PageId> {
+        let page_id = self.reusable.pop()?;
+        self.freed.remove(&page_id);
+        Some(page_id)
+    }
+
+    // pages allocated in batch and not used yet
+    pub fn add_allocated(&mut self, page_ids: Vec<PageId>) {
+        self.freed.extend(&page_ids);
+        self.reusable.extend(page_ids);
+    }
+
+    // pages freed before checkpoint starts. they are given back by
+    // `add_reusable` or `restore_pending` when it ends.
+    pub fn take_pending(&mut self) -> Vec<PageId> {
+        std::mem::take(&mut self.pending)
+    }
+
+    pub fn restore_pending(&mut self, page_ids: Vec<PageId>) {
+        self.freed.extend(&page_ids);
+        self.pending.splice(0..0, page_ids);
+    }
+
+    // returns pages beyond `max_reusable`, which are to be deallocated
+    pub fn add_reusable(&mut self, page_ids: Vec<PageId>, max_reusable: usize) -> Vec<PageId> {
+        self.reusable.extend(page_ids);
+        let excess = self.reusable.len().saturating_sub(max_reusable);
+        self.take(excess)
+    }
+
+    // every page, to be deallocated on shutdown
+    pub fn take_all(&mut self) -> Vec<PageId> {
+        self.reusable.append(&mut self.pending);
+        self.take(self.reusable.len())
+    }
+
+    fn take(&mut self, num_pages: usize) -> Vec<PageId> {
+        let page_ids: Vec<PageId> = self.reusable.drain(..num_pages).collect();
+        for page_id in &page_ids {
+            self.freed.remove(page_id);
+        }
+        page_ids
+    }
+}
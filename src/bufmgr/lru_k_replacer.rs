@@ -0,0 +1,143 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::types::PageId;
+use super::Replacer;
+
+// evict page whose k-th most recent access is the oldest.
+// pages accessed less than k times are evicted first, in order of first access.
+// history of evicted pages is retained for a while so that re-read pages
+// can reach k accesses.
+pub struct LruKReplacer {
+    k: usize,
+    retained: usize,
+    clock: u64,
+    entries: HashMap<PageId, LruKEntry>,
+    evictable_young: BTreeMap<u64, PageId>, // less than k accesses. earliest access -> page
+    evictable_old: BTreeMap<u64, PageId>, // k-th most recent access -> page
+    evicted: HashMap<PageId, VecDeque<u64>>,
+    evicted_order: VecDeque<PageId>,
+}
+
+struct LruKEntry {
+    history: VecDeque<u64>, // at most k timestamps, oldest first
+    evictable: bool,
+}
+
+impl LruKReplacer {
+    pub fn new(k: usize, retained: usize) -> LruKReplacer {
+        LruKReplacer {
+            k: k.max(1),
+            retained,
+            clock: 0,
+            entries: HashMap::new(),
+            evictable_young: BTreeMap::new(),
+            evictable_old: BTreeMap::new(),
+            evicted: HashMap::new(),
+            evicted_order: VecDeque::new(),
+        }
+    }
+
+    fn retain_history(&mut self, page_id: PageId, history: VecDeque<u64>) {
+        self.evicted.insert(page_id, history);
+        self.evicted_order.push_back(page_id);
+        while self.evicted_order.len() > self.retained {
+            let old = self.evicted_order.pop_front().unwrap();
+            self.evicted.remove(&old);
+        }
+    }
+
+    fn set_evictable_entry(&mut self, page_id: PageId, evictable: bool) {
+        let entry = self.entries.get_mut(&page_id).unwrap();
+        entry.evictable = evictable;
+        let ts = *entry.history.front().unwrap();
+        let queue = if entry.history.len() < self.k {
+            &mut self.evictable_young
+        } else {
+            &mut self.evictable_old
+        };
+        if evictable {
+            queue.insert(ts, page_id);
+        } else {
+            queue.remove(&ts);
+        }
+    }
+}
+
+impl Replacer for LruKReplacer {
+    fn record_access(&mut self, page_id: PageId) {
+        self.clock += 1;
+        let evictable = match self.entries.get(&page_id) {
+            Some(entry) => entry.evictable,
+            None => {
+                let history = match self.evicted.remove(&page_id) {
+                    Some(history) => {
+                        self.evicted_order.retain(|pid| *pid != page_id);
+                        history
+                    }
+                    None => VecDeque::new(),
+                };
+                self.entries.insert(page_id, LruKEntry { history, evictable: false });
+                false
+            }
+        };
+        if evictable {
+            self.set_evictable_entry(page_id, false);
+        }
+        let entry = self.entries.get_mut(&page_id).unwrap();
+        entry.history.push_back(self.clock);
+        if entry.history.len() > self.k {
+            entry.history.pop_front();
+        }
+        if evictable {
+            self.set_evictable_entry(page_id, true);
+        }
+    }
+
+    fn set_evictable(&mut self, page_id: PageId, evictable: bool) {
+        match self.entries.get(&page_id) {
+            None => self.record_access(page_id),
+            Some(entry) if entry.evictable == evictable => return,
+            Some(_) => {}
+        }
+        self.set_evictable_entry(page_id, evictable);
+    }
+
+    fn evict(&mut self) -> Option<PageId> {
+        let (_, page_id) = self.evictable_young.pop_first()
+            .or_else(|| self.evictable_old.pop_first())?;
+        let entry = self.entries.remove(&page_id).unwrap();
+        self.retain_history(page_id, entry.history);
+        Some(page_id)
+    }
+
+    fn remove(&mut self, page_id: PageId) {
+        if self.entries.get(&page_id).is_some_and(|e| e.evictable) {
+            self.set_evictable_entry(page_id, false);
+        }
+        self.entries.remove(&page_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_k_replacer() {
+        let mut r = LruKReplacer::new(2, 0);
+        for pid in [1, 2, 3, 1, 2, 4] {
+            r.record_access(pid);
+            r.set_evictable(pid, true);
+        }
+        // 3 and 4 are accessed only once
+        assert_eq!(Some(3), r.evict());
+        assert_eq!(Some(4), r.evict());
+        // 2nd most recent access of 1 is older than 2
+        r.record_access(2);
+        assert_eq!(Some(1), r.evict());
+        r.set_evictable(2, false);
+        assert_eq!(None, r.evict());
+        r.remove(2);
+        assert!(r.entries.is_empty());
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::types::PageId;
+use super::Replacer;
+
+// evict least recently used page
+pub struct LruReplacer {
+    clock: u64,
+    last_access: HashMap<PageId, u64>,
+    evictable: BTreeMap<u64, PageId>, // last access -> page
+}
+
+impl LruReplacer {
+    pub fn new() -> LruReplacer {
+        LruReplacer {
+            clock: 0,
+            last_access: HashMap::new(),
+            evictable: BTreeMap::new(),
+        }
+    }
+}
+
+impl Replacer for LruReplacer {
+    fn record_access(&mut self, page_id: PageId) {
+        self.clock += 1;
+        if let Some(ts) = self.last_access.insert(page_id, self.clock) {
+            if self.evictable.remove(&ts).is_some() {
+                self.evictable.insert(self.clock, page_id);
+            }
+        }
+    }
+
+    fn set_evictable(&mut self, page_id: PageId, evictable: bool) {
+        if !self.last_access.contains_key(&page_id) {
+            self.record_access(page_id);
+        }
+        let ts = self.last_access[&page_id];
+        if evictable {
+            self.evictable.insert(ts, page_id);
+        } else {
+            self.evictable.remove(&ts);
+        }
+    }
+
+    fn evict(&mut self) -> Option<PageId> {
+        let (_, page_id) = self.evictable.pop_first()?;
+        self.last_access.remove(&page_id);
+        Some(page_id)
+    }
+
+    fn remove(&mut self, page_id: PageId) {
+        if let Some(ts) = self.last_access.remove(&page_id) {
+            self.evictable.remove(&ts);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_replacer() {
+        let mut r = LruReplacer::new();
+        for pid in 1..=3 {
+            r.record_access(pid);
+            r.set_evictable(pid, true);
+        }
+        r.record_access(1);
+        r.set_evictable(2, false);
+        assert_eq!(Some(3), r.evict());
+        assert_eq!(Some(1), r.evict());
+        assert_eq!(None, r.evict());
+        r.set_evictable(2, true);
+        r.remove(2);
+        assert_eq!(None, r.evict());
+    }
+}
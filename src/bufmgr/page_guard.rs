@@ -0,0 +1,148 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{RwLockReadGuard, RwLockWriteGuard, TryLockError};
+
+use crate::types::{Res, Error, PageId, EntryNo};
+use crate::page::Page;
+use super::{BufMgr, BufferRing, StatCounters};
+
+// pinned page under shared latch. unlatched and unpinned when dropped.
+pub struct ReadPageGuard<'a> {
+    bufmgr: &'a BufMgr,
+    page_id: PageId,
+    idx: usize,
+    latch: Option<RwLockReadGuard<'a, Page>>,
+}
+
+// pinned page under exclusive latch. frame is marked dirty when page is
+// borrowed mutably.
+pub struct WritePageGuard<'a> {
+    bufmgr: &'a BufMgr,
+    page_id: PageId,
+    idx: usize,
+    latch: Option<RwLockWriteGuard<'a, Page>>,
+}
+
+impl BufMgr {
+    pub fn fetch_page_read(&self, page_id: PageId) -> Res<ReadPageGuard<'_>> {
+        self.fetch_page_read_in(page_id, None)
+    }
+
+    pub fn fetch_page_read_in(&self, page_id: PageId, ring: Option<&BufferRing>) -> Res<ReadPageGuard<'_>> {
+        let idx = self.pin_frame(page_id, ring)?;
+        let latch = match self.frames[idx].latch.try_read() {
+            Ok(latch) => Some(latch),
+            Err(TryLockError::WouldBlock) => {
+                StatCounters::incr(&self.stats.pin_waits);
+                self.frames[idx].latch.read().ok()
+            }
+            Err(TryLockError::Poisoned(_)) => None,
+        };
+        let Some(latch) = latch else {
+            return Err(self.latch_poisoned(page_id));
+        };
+        Ok(ReadPageGuard { bufmgr: self, page_id, idx, latch: Some(latch) })
+    }
+
+    pub fn fetch_page_write(&self, page_id: PageId) -> Res<WritePageGuard<'_>> {
+        let idx = self.pin_frame(page_id, None)?;
+        let latch = match self.frames[idx].latch.try_write() {
+            Ok(latch) => Some(latch),
+            Err(TryLockError::WouldBlock) => {
+                StatCounters::incr(&self.stats.pin_waits);
+                self.frames[idx].latch.write().ok()
+            }
+            Err(TryLockError::Poisoned(_)) => None,
+        };
+        let Some(latch) = latch else {
+            return Err(self.latch_poisoned(page_id));
+        };
+        Ok(WritePageGuard { bufmgr: self, page_id, idx, latch: Some(latch) })
+    }
+
+    // thread panicked while holding exclusive latch, so the page may be modified
+    // partly. pin taken for the fetch is released.
+    fn latch_poisoned(&self, page_id: PageId) -> Error {
+        log::error!("BufMgr: latch is poisoned. page_id={}", page_id);
+        if let Err(e) = self.unpin_frame(page_id) {
+            log::error!("BufMgr: failed to unpin page. page_id={} {:?}", page_id, e);
+        }
+        Error::Corruption { page_id }
+    }
+
+    pub fn new_page(&self) -> Res<WritePageGuard<'_>> {
+        let reused = self.free_pages.lock().unwrap().pop_reusable();
+        let page_id = match reused {
+            Some(page_id) => page_id,
+            None => self.allocate_page()?,
+        };
+        let mut guard = match self.fetch_page_write(page_id) {
+            Ok(guard) => guard,
+            Err(e) => {
+                self.free_pages.lock().unwrap().restore_pending(vec![page_id]);
+                return Err(e);
+            }
+        };
+        // allocated page may hold contents of a deallocated one
+        guard.get_data_mut().fill(0);
+        Ok(guard)
+    }
+}
+
+impl ReadPageGuard<'_> {
+    // record heap file owning the page for introspection
+    pub fn set_owner(&self, owner: EntryNo) {
+        self.bufmgr.frames[self.idx].set_owner(Some(owner));
+    }
+}
+
+impl WritePageGuard<'_> {
+    pub fn get_page_id(&self) -> PageId {
+        self.page_id
+    }
+
+    pub fn set_owner(&self, owner: EntryNo) {
+        self.bufmgr.frames[self.idx].set_owner(Some(owner));
+    }
+}
+
+impl Deref for ReadPageGuard<'_> {
+    type Target = Page;
+
+    fn deref(&self) -> &Page {
+        self.latch.as_ref().unwrap()
+    }
+}
+
+impl Deref for WritePageGuard<'_> {
+    type Target = Page;
+
+    fn deref(&self) -> &Page {
+        self.latch.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for WritePageGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Page {
+        self.bufmgr.frames[self.idx].set_dirty(true);
+        self.latch.as_mut().unwrap()
+    }
+}
+
+impl Drop for ReadPageGuard<'_> {
+    fn drop(&mut self) {
+        // release latch before the frame becomes evictable
+        self.latch.take();
+        if let Err(e) = self.bufmgr.unpin_frame(self.page_id) {
+            log::error!("ReadPageGuard::drop: failed to unpin page. page_id={} {:?}", self.page_id, e);
+        }
+    }
+}
+
+impl Drop for WritePageGuard<'_> {
+    fn drop(&mut self) {
+        self.latch.take();
+        if let Err(e) = self.bufmgr.unpin_frame(self.page_id) {
+            log::error!("WritePageGuard::drop: failed to unpin page. page_id={} {:?}", self.page_id, e);
+        }
+    }
+}
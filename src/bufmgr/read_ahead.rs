@@ -0,0 +1,77 @@
+use std::sync::{Arc, Weak};
+use std::sync::mpsc::{self, Sender};
+use std::thread::JoinHandle;
+
+use crate::types::{Res, PageId};
+use crate::page::Page;
+use super::{BufMgr, BufferRing};
+
+// thread loading pages ahead of a sequential scan. pages are chained and
+// `next_page` reads id of the following page from a page (0 for the last).
+pub struct ReadAhead {
+    tx: Option<Sender<PageId>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ReadAhead {
+    pub fn start(
+        bufmgr: &Arc<BufMgr>,
+        ring: Option<Arc<BufferRing>>,
+        num_pages: usize,
+        next_page: fn(&Page) -> Res<PageId>,
+    ) -> ReadAhead {
+        let bufmgr: Weak<BufMgr> = Arc::downgrade(bufmgr);
+        let (tx, rx) = mpsc::channel::<PageId>();
+        let handle = std::thread::spawn(move || {
+            while let Ok(page_id) = rx.recv() {
+                // scan has moved on. only the latest hint matters
+                let page_id = rx.try_iter().last().unwrap_or(page_id);
+                let Some(bufmgr) = bufmgr.upgrade() else {
+                    break;
+                };
+                if let Err(e) = prefetch(&bufmgr, ring.as_deref(), page_id, num_pages, next_page) {
+                    log::debug!("ReadAhead: stop prefetch at page_id={}: {:?}", page_id, e);
+                }
+            }
+        });
+        ReadAhead {
+            tx: Some(tx),
+            handle: Some(handle),
+        }
+    }
+
+    // hint that scan is reading `page_id`
+    pub fn advance(&self, page_id: PageId) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(page_id);
+        }
+    }
+}
+
+fn prefetch(
+    bufmgr: &BufMgr,
+    ring: Option<&BufferRing>,
+    page_id: PageId,
+    num_pages: usize,
+    next_page: fn(&Page) -> Res<PageId>,
+) -> Res<()> {
+    // current page is resident, so walking from it is cheap
+    let mut page_id = page_id;
+    for _ in 0..=num_pages {
+        if page_id == 0 {
+            break;
+        }
+        let page = bufmgr.fetch_page_read_in(page_id, ring)?;
+        page_id = next_page(&page)?;
+    }
+    Ok(())
+}
+
+impl Drop for ReadAhead {
+    fn drop(&mut self) {
+        self.tx.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::types::{PageId, EntryNo};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BufMgrStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub dirty_writes: u64, // pages written back by eviction or flush
+    pub pin_waits: u64, // latch requests blocked by other threads
+}
+
+#[derive(Clone, Debug)]
+pub struct FrameInfo {
+    pub frame_no: usize,
+    pub page_id: PageId,
+    pub pin_count: usize,
+    pub dirty: bool,
+    pub owner: Option<EntryNo>, // heap file which accessed the page
+}
+
+#[derive(Default)]
+pub(super) struct StatCounters {
+    pub hits: AtomicU64,
+    pub misses: AtomicU64,
+    pub evictions: AtomicU64,
+    pub dirty_writes: AtomicU64,
+    pub pin_waits: AtomicU64,
+}
+
+impl StatCounters {
+    pub fn incr(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> BufMgrStats {
+        BufMgrStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            dirty_writes: self.dirty_writes.load(Ordering::Relaxed),
+            pin_waits: self.pin_waits.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use crate::types::PageId;
+use super::Replacer;
+
+// 2Q (Johnson and Shasha). new pages enter FIFO queue A1in. pages evicted
+// from A1in are remembered in ghost queue A1out, and promoted to LRU queue Am
+// if they are accessed again.
+pub struct TwoQReplacer {
+    clock: u64,
+    kin: usize,
+    kout: usize,
+    entries: HashMap<PageId, TwoQEntry>,
+    num_a1in: usize,
+    a1in: BTreeMap<u64, PageId>, // evictable pages. first access -> page
+    am: BTreeMap<u64, PageId>, // evictable pages. last access -> page
+    a1out: VecDeque<PageId>,
+    a1out_set: HashSet<PageId>,
+}
+
+struct TwoQEntry {
+    in_am: bool,
+    ts: u64,
+    evictable: bool,
+}
+
+impl TwoQReplacer {
+    pub fn new(capacity: usize) -> TwoQReplacer {
+        TwoQReplacer {
+            clock: 0,
+            kin: (capacity / 4).max(1),
+            kout: capacity.max(1),
+            entries: HashMap::new(),
+            num_a1in: 0,
+            a1in: BTreeMap::new(),
+            am: BTreeMap::new(),
+            a1out: VecDeque::new(),
+            a1out_set: HashSet::new(),
+        }
+    }
+
+    fn queue(&mut self, in_am: bool) -> &mut BTreeMap<u64, PageId> {
+        if in_am { &mut self.am } else { &mut self.a1in }
+    }
+
+    fn remember(&mut self, page_id: PageId) {
+        self.a1out.push_back(page_id);
+        self.a1out_set.insert(page_id);
+        if self.a1out.len() > self.kout {
+            let old = self.a1out.pop_front().unwrap();
+            self.a1out_set.remove(&old);
+        }
+    }
+}
+
+impl Replacer for TwoQReplacer {
+    fn record_access(&mut self, page_id: PageId) {
+        self.clock += 1;
+        let clock = self.clock;
+        if let Some(entry) = self.entries.get_mut(&page_id) {
+            // access in A1in is regarded as correlated and ignored
+            if entry.in_am {
+                let (old_ts, evictable) = (entry.ts, entry.evictable);
+                entry.ts = clock;
+                if evictable {
+                    self.am.remove(&old_ts);
+                    self.am.insert(clock, page_id);
+                }
+            }
+            return;
+        }
+        let in_am = self.a1out_set.remove(&page_id);
+        if in_am {
+            self.a1out.retain(|pid| *pid != page_id);
+        } else {
+            self.num_a1in += 1;
+        }
+        self.entries.insert(page_id, TwoQEntry { in_am, ts: clock, evictable: false });
+    }
+
+    fn set_evictable(&mut self, page_id: PageId, evictable: bool) {
+        if !self.entries.contains_key(&page_id) {
+            self.record_access(page_id);
+        }
+        let entry = self.entries.get_mut(&page_id).unwrap();
+        if entry.evictable == evictable {
+            return;
+        }
+        entry.evictable = evictable;
+        let (in_am, ts) = (entry.in_am, entry.ts);
+        if evictable {
+            self.queue(in_am).insert(ts, page_id);
+        } else {
+            self.queue(in_am).remove(&ts);
+        }
+    }
+
+    fn evict(&mut self) -> Option<PageId> {
+        let from_a1in = self.num_a1in > self.kin || self.am.is_empty();
+        let victim = if from_a1in {
+            self.a1in.pop_first().or_else(|| self.am.pop_first())
+        } else {
+            self.am.pop_first().or_else(|| self.a1in.pop_first())
+        };
+        let (_, page_id) = victim?;
+        let entry = self.entries.remove(&page_id).unwrap();
+        if !entry.in_am {
+            self.num_a1in -= 1;
+            self.remember(page_id);
+        }
+        Some(page_id)
+    }
+
+    fn remove(&mut self, page_id: PageId) {
+        if let Some(entry) = self.entries.remove(&page_id) {
+            if entry.evictable {
+                self.queue(entry.in_am).remove(&entry.ts);
+            }
+            if !entry.in_am {
+                self.num_a1in -= 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_q_replacer() {
+        let mut r = TwoQReplacer::new(4); // kin=1, kout=4
+        for pid in 1..=3 {
+            r.record_access(pid);
+            r.set_evictable(pid, true);
+        }
+        // A1in is evicted in FIFO order and remembered in A1out
+        assert_eq!(Some(1), r.evict());
+        assert_eq!(Some(2), r.evict());
+        // 1 is accessed again. promoted to Am
+        r.record_access(1);
+        r.set_evictable(1, true);
+        assert!(r.entries[&1].in_am);
+        r.record_access(4);
+        r.set_evictable(4, true);
+        // A1in has 3 and 4, more than kin
+        assert_eq!(Some(3), r.evict());
+        assert_eq!(Some(1), r.evict());
+        assert_eq!(Some(4), r.evict());
+        assert_eq!(None, r.evict());
+        r.remove(2);
+        assert!(r.entries.is_empty());
+    }
+}
@@ -18,6 +18,6 @@ mod tests {
         let mut xs = [0; 20];
         let x = 9;
         set_int_value(&mut xs, 10, x);
-        assert_eq!(x, get_int_value(&mut xs, 10).unwrap());
+        assert_eq!(x, get_int_value(&xs, 10).unwrap());
     }
 }
\ No newline at end of file
@@ -1,18 +1,120 @@
+pub mod mem_page_store;
+pub mod fault_page_store;
+
+pub use mem_page_store::*;
+pub use fault_page_store::*;
+
 use std::fs::File;
 use std::io::{Seek, SeekFrom, Read, Write};
 
 use super::types::*;
-use super::page::{Page, PAGE_BYTE};
+use super::page::{Page, PAGE_BYTE, PAGE_HEADER_BYTE, MIN_PAGE_BYTE};
+
+// storage backend used by BufMgr
+pub trait PageStore: Send {
+    fn get_page_byte(&self) -> usize;
+    fn read_page(&mut self, page_id: PageId) -> Res<Page>;
+    fn write_page(&mut self, page_id: PageId, page: &Page) -> Res<()>;
+    // returned page is zero-filled. page id 0 is never returned.
+    fn allocate(&mut self) -> Res<PageId>;
+    fn deallocate(&mut self, page_id: PageId) -> Res<()>;
+    fn sync(&mut self) -> Res<()>;
+    // root page of file directory. 0 if not created yet.
+    fn get_catalog_root(&self) -> PageId;
+    fn set_catalog_root(&mut self, page_id: PageId) -> Res<()>;
+}
 
 pub struct DiskMgr {
     name: String,
     fp: File,
-    // bitmap_page: Page,
+    page_byte: usize,
+    super_block: SuperBlock,
+    // cached free-space map. map_pages[k] covers page ids
+    // [k * map_bits_per_page, (k + 1) * map_bits_per_page)
+    map_pages: Vec<MapPage>,
+    free_hint: PageId, // every page below this id is allocated
+}
+
+struct MapPage {
+    page_id: PageId,
+    page: Page,
 }
 
-const NUM_PAGES: usize = 10; // each file has 10 pages
-const BITMAP_PAGE_ID: PageId = 0; // the first page is bitmap page
-const FIRST_PAGE_ID: PageId = 1;
+pub const MAGIC: &[u8; 8] = b"nanodb\0\0";
+// raised whenever on-disk layout changes, so that older files are rejected
+pub const FORMAT_VERSION: u32 = 4;
+
+const SUPER_BLOCK_PAGE_ID: PageId = 0; // the first page is super block
+const FIRST_MAP_PAGE_ID: PageId = 1; // followed by the first bitmap page
+
+// super block format
+// magic: 8
+// format_version: 4
+// page_byte: 4
+// num_pages: 4
+// map_root_page_id: 4
+// catalog_root_page_id: 4
+const SUPER_MAGIC: usize = 0;
+const SUPER_FORMAT_VERSION: usize = 8;
+const SUPER_PAGE_BYTE: usize = 12;
+const SUPER_NUM_PAGES: usize = 16;
+const SUPER_MAP_ROOT_PAGE_ID: usize = 20;
+const SUPER_CATALOG_ROOT_PAGE_ID: usize = 24;
+
+const MAP_NEXT_PAGE_ID: usize = 0;
+const MAP_BITS_START: usize = 4;
+
+struct SuperBlock {
+    page_byte: usize,
+    num_pages: usize,
+    map_root_page_id: PageId,
+    catalog_root_page_id: PageId, // 0 if catalog is not created yet
+}
+
+impl SuperBlock {
+    fn new(page_byte: usize) -> Self {
+        Self {
+            page_byte,
+            num_pages: 0,
+            map_root_page_id: FIRST_MAP_PAGE_ID,
+            catalog_root_page_id: 0,
+        }
+    }
+
+    // `page` may be a prefix of the super block page, since page size is unknown before decoding.
+    fn decode(page: &Page) -> Res<SuperBlock> {
+        if &page.get_data()[SUPER_MAGIC..SUPER_MAGIC + MAGIC.len()] != MAGIC {
+            return Err(Error::InvalidFormat { msg: "magic number mismatch".to_string() });
+        }
+        let version = page.get_int_value(SUPER_FORMAT_VERSION)? as u32;
+        if version != FORMAT_VERSION {
+            return Err(Error::InvalidFormat {
+                msg: format!("unsupported format version {} (expected {})", version, FORMAT_VERSION)
+            });
+        }
+        let page_byte = page.get_int_value(SUPER_PAGE_BYTE)? as usize;
+        if !Page::is_valid_page_byte(page_byte) {
+            return Err(Error::InvalidFormat { msg: format!("invalid page size {}", page_byte) });
+        }
+        Ok(SuperBlock {
+            page_byte,
+            num_pages: page.get_int_value(SUPER_NUM_PAGES)? as usize,
+            map_root_page_id: page.get_int_value(SUPER_MAP_ROOT_PAGE_ID)? as PageId,
+            catalog_root_page_id: page.get_int_value(SUPER_CATALOG_ROOT_PAGE_ID)? as PageId,
+        })
+    }
+
+    fn encode(&self) -> Res<Page> {
+        let mut page = Page::new(self.page_byte);
+        page.get_data_mut()[SUPER_MAGIC..SUPER_MAGIC + MAGIC.len()].copy_from_slice(MAGIC);
+        page.set_int_value(SUPER_FORMAT_VERSION, FORMAT_VERSION as i32)?;
+        page.set_int_value(SUPER_PAGE_BYTE, self.page_byte as i32)?;
+        page.set_int_value(SUPER_NUM_PAGES, self.num_pages as i32)?;
+        page.set_int_value(SUPER_MAP_ROOT_PAGE_ID, self.map_root_page_id as i32)?;
+        page.set_int_value(SUPER_CATALOG_ROOT_PAGE_ID, self.catalog_root_page_id as i32)?;
+        Ok(page)
+    }
+}
 
 impl DiskMgr {
     // pub fn new(name: String) -> Self {
@@ -21,106 +123,318 @@ impl DiskMgr {
     //     }
     // }
 
+    // open existing database with its own page size, or create new one with default page size.
     pub fn open_db(name: &str) -> Res<DiskMgr> {
+        Self::open(name, None)
+    }
+
+    // page size of existing database must be equal to `page_byte`.
+    pub fn open_db_with_page_byte(name: &str, page_byte: usize) -> Res<DiskMgr> {
+        if !Page::is_valid_page_byte(page_byte) {
+            return Err(Error::InvalidArg {
+                msg: format!("DiskMgr::open_db_with_page_byte: invalid page size {}", page_byte)
+            });
+        }
+        Self::open(name, Some(page_byte))
+    }
+
+    fn open(name: &str, page_byte: Option<usize>) -> Res<DiskMgr> {
         let fp = std::fs::OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(name)
-            .and_then(|f|{
-                f.set_len((PAGE_BYTE * NUM_PAGES) as u64)?;
-                Ok(f)
-            })
-            .map_err(|e| Error::IoError(e))?;
-        let diskmgr = DiskMgr {
+            .map_err(Error::IoError)?;
+        let len = fp.metadata().map_err(Error::IoError)?.len() as usize;
+        let page_byte_new = page_byte.unwrap_or(PAGE_BYTE);
+        let mut diskmgr = DiskMgr {
             name: name.to_string(),
             fp,
-            // bitmap_page,
+            page_byte: page_byte_new,
+            super_block: SuperBlock::new(page_byte_new),
+            map_pages: Vec::new(),
+            free_hint: 0,
         };
+        if len == 0 {
+            diskmgr.init_db()?;
+        } else {
+            diskmgr.load_db(len)?;
+            if page_byte.is_some_and(|b| b != diskmgr.page_byte) {
+                return Err(Error::InvalidFormat {
+                    msg: format!("page size mismatch {} (expected {})", diskmgr.page_byte, page_byte_new)
+                });
+            }
+        }
         Ok(diskmgr)
     }
 
+    fn init_db(&mut self) -> Res<()> {
+        self.extend(SUPER_BLOCK_PAGE_ID + 1)?;
+        self.add_map_page(FIRST_MAP_PAGE_ID)?;
+        self.set_used(SUPER_BLOCK_PAGE_ID, true)?;
+        Ok(())
+    }
+
+    fn load_db(&mut self, len: usize) -> Res<()> {
+        if len < MIN_PAGE_BYTE {
+            return Err(Error::InvalidFormat { msg: format!("file is too small. size={}", len) });
+        }
+        // check format before checksum, since other versions may differ in page header
+        let head = Self::read_page_no_check(&mut self.fp, SUPER_BLOCK_PAGE_ID, MIN_PAGE_BYTE)?;
+        self.super_block = SuperBlock::decode(&head)?;
+        self.page_byte = self.super_block.page_byte;
+        if len < self.super_block.num_pages * self.page_byte {
+            return Err(Error::InvalidFormat {
+                msg: format!("file is truncated. size={}, num_pages={}", len, self.super_block.num_pages)
+            });
+        }
+        let page = Self::read_page_no_check(&mut self.fp, SUPER_BLOCK_PAGE_ID, self.page_byte)?;
+        page.verify_checksum(SUPER_BLOCK_PAGE_ID)?;
+        self.load_map_pages()
+    }
+
     pub fn close_db(&mut self) -> Res<()> {
-        self.fp.sync_all().map_err(|e| Error::IoError(e))?;
+        self.fp.sync_all().map_err(Error::IoError)?;
         Ok(())
     }
 
-    fn read_page_no_check(fp: &mut File, page_id: PageId) -> Res<Page> {
-        let mut page = Page::new();
-        // let mut buf = vec![0; PAGE_BYTE];
-        fp.seek(SeekFrom::Start((page_id * PAGE_BYTE) as u64))
-        .and_then(|_| fp.read_exact(&mut page.get_data_mut()))
-        .map_err(|e| Error::IoError(e))?;
-        // page.set_data(&buf)?;
-        Ok(page)        
+    pub fn get_page_byte(&self) -> usize {
+        self.page_byte
     }
 
-    fn read_bitmap_page(&mut self) -> Res<Page> {
-        let mut page = Page::new();
+    pub fn get_num_pages(&self) -> usize {
+        self.super_block.num_pages
+    }
+
+    pub fn get_catalog_root(&self) -> PageId {
+        self.super_block.catalog_root_page_id
+    }
+
+    pub fn set_catalog_root(&mut self, page_id: PageId) -> Res<()> {
+        self.super_block.catalog_root_page_id = page_id;
+        self.write_super_block()
+    }
+
+    fn write_super_block(&mut self) -> Res<()> {
+        let page = self.super_block.encode()?;
+        self.write_page_no_check(SUPER_BLOCK_PAGE_ID, &page)
+    }
+
+    fn extend(&mut self, num_pages: usize) -> Res<()> {
+        self.fp.set_len((num_pages * self.page_byte) as u64).map_err(Error::IoError)?;
+        self.super_block.num_pages = num_pages;
+        self.write_super_block()
+    }
+
+    // read page without checking allocation nor checksum
+    fn read_page_no_check(fp: &mut File, page_id: PageId, page_byte: usize) -> Res<Page> {
+        let mut page = Page::new(page_byte);
         // let mut buf = vec![0; PAGE_BYTE];
-        self.fp.seek(SeekFrom::Start(BITMAP_PAGE_ID as u64 * PAGE_BYTE as u64))
-        .and_then(|_| self.fp.read_exact(&mut page.get_data_mut()))
-        .map_err(|e| Error::IoError(e))?;
+        fp.seek(SeekFrom::Start((page_id * page_byte) as u64))
+        .and_then(|_| fp.read_exact(page.get_raw_data_mut()))
+        .map_err(Error::IoError)?;
         // page.set_data(&buf)?;
         Ok(page)        
     }
 
-    fn write_bitmap_page(&mut self, page: &Page) -> Res<()> {
-        // let buf = page.get_data();
-        self.fp.seek(SeekFrom::Start(0))
-        .and_then(|_| self.fp.write_all(&page.get_data()))
-        .map_err(|e| Error::IoError(e))?;
+    fn write_page_no_check(&mut self, page_id: PageId, page: &Page) -> Res<()> {
+        if page.get_page_byte() != self.page_byte {
+            return Err(Error::InvalidArg {
+                msg: format!("DiskMgr::write_page: page size {} differs from {}", page.get_page_byte(), self.page_byte)
+            });
+        }
+        let mut page = page.clone();
+        page.update_checksum(page_id);
+        self.fp.seek(SeekFrom::Start((page_id * self.page_byte) as u64))
+        .and_then(|_| self.fp.write_all(page.get_raw_data()))
+        .map_err(Error::IoError)?;
         Ok(())
     }
 
+    fn load_map_pages(&mut self) -> Res<()> {
+        let mut page_id = self.super_block.map_root_page_id;
+        loop {
+            let expected = self.map_page_id(self.map_pages.len());
+            if page_id != expected || page_id >= self.get_num_pages() {
+                return Err(Error::InvalidFormat {
+                    msg: format!("broken map page chain. page_id={}, expected={}", page_id, expected)
+                });
+            }
+            let page = Self::read_page_no_check(&mut self.fp, page_id, self.page_byte)?;
+            page.verify_checksum(page_id)?;
+            let next_page_id = page.get_int_value(MAP_NEXT_PAGE_ID)? as PageId;
+            self.map_pages.push(MapPage { page_id, page });
+            if next_page_id == 0 {
+                return Ok(());
+            }
+            page_id = next_page_id;
+        }
+    }
+
+    fn map_bits_per_page(&self) -> usize {
+        (self.page_byte - PAGE_HEADER_BYTE - MAP_BITS_START) * 8
+    }
+
+    // the k-th map page is placed on the first page of the range it covers,
+    // except for the first one which follows the super block.
+    fn map_page_id(&self, k: usize) -> PageId {
+        if k == 0 {
+            FIRST_MAP_PAGE_ID
+        } else {
+            k * self.map_bits_per_page()
+        }
+    }
+
+    fn is_map_page(&self, page_id: PageId) -> bool {
+        page_id == FIRST_MAP_PAGE_ID || (page_id > 0 && page_id.is_multiple_of(self.map_bits_per_page()))
+    }
+
+    // append a new map page at the end of file.
+    fn add_map_page(&mut self, page_id: PageId) -> Res<()> {
+        debug_assert_eq!(page_id, self.map_page_id(self.map_pages.len()));
+        debug_assert_eq!(page_id, self.get_num_pages());
+        self.extend(page_id + 1)?;
+        let mut page = Page::new(self.page_byte);
+        page.set_int_value(MAP_NEXT_PAGE_ID, 0)?;
+        self.write_page_no_check(page_id, &page)?;
+        if let Some(mut last) = self.map_pages.pop() {
+            last.page.set_int_value(MAP_NEXT_PAGE_ID, page_id as i32)?;
+            self.write_page_no_check(last.page_id, &last.page)?;
+            self.map_pages.push(last);
+        }
+        self.map_pages.push(MapPage { page_id, page });
+        self.set_used(page_id, true) // map page itself is used
+    }
+
+    fn bit_position(&self, page_id: PageId) -> (usize, usize, u8) {
+        let bits = self.map_bits_per_page();
+        let bit = page_id % bits;
+        (page_id / bits, MAP_BITS_START + bit / 8, 1 << (bit % 8))
+    }
+
+    fn get_map_byte(&self, page_id: PageId) -> Res<(u8, u8)> {
+        let (k, offset, mask) = self.bit_position(page_id);
+        let byte = self.map_pages[k].page.get_byte_value(offset)?;
+        Ok((byte, mask))
+    }
+
+    fn set_used(&mut self, page_id: PageId, used: bool) -> Res<()> {
+        let (k, offset, mask) = self.bit_position(page_id);
+        let map_page = &mut self.map_pages[k];
+        let byte = map_page.page.get_byte_value(offset)?;
+        let new_byte = if used { byte | mask } else { byte & !mask };
+        if byte == new_byte {
+            return Ok(());
+        }
+        map_page.page.set_byte_value(offset, new_byte)?;
+        let map_page_id = map_page.page_id;
+        let page = map_page.page.clone();
+        self.write_page_no_check(map_page_id, &page)
+    }
+
     pub fn read_page(&mut self, page_no: PageId) -> Res<Page> {
         if self.is_free_page(page_no)? {
             return Err(Error::InvalidArg { msg: "not allocated page".to_string() });
         }
-        Self::read_page_no_check(&mut self.fp, page_no)
+        let page = Self::read_page_no_check(&mut self.fp, page_no, self.page_byte)?;
+        page.verify_checksum(page_no)?;
+        Ok(page)
     }
 
     pub fn write_page(&mut self, page_no: PageId, page: &Page) -> Res<()> {
         if self.is_free_page(page_no)? {
             return Err(Error::InvalidArg { msg: "not allocated page".to_string() });
         }
-        // let buf = page.get_data();
-        self.fp.seek(SeekFrom::Start((page_no * PAGE_BYTE) as u64))
-        .and_then(|_| self.fp.write_all(&page.get_data()))
-        .map_err(|e| Error::IoError(e))?;
-        Ok(())
+        self.write_page_no_check(page_no, page)
     }
 
     pub fn is_free_page(&mut self, page_id: PageId) -> Res<bool> {
-        let bitmap_page = self.read_bitmap_page()?;
-        let byte = bitmap_page.get_byte_value(page_id)?;
-        Ok(byte == 0)
-    }
-
-    pub fn set_page_type(&mut self, page_id: PageId, page_type: u8) -> Res<()> {
-        let mut bitmap_page = self.read_bitmap_page()?;
-        let byte = bitmap_page.get_byte_value(page_id)?;
-        if byte == page_type {
-            return Ok(());
+        if page_id >= self.get_num_pages() {
+            return Ok(true);
         }
-        bitmap_page.set_byte_value(page_id, page_type)?;
-        self.write_bitmap_page(&bitmap_page)?;
-        Ok(())
+        let (byte, mask) = self.get_map_byte(page_id)?;
+        Ok(byte & mask == 0)
     }
 
     pub fn allocate_page(&mut self) -> Res<PageId> {
-        let bitmap_page = self.read_bitmap_page()?;
-        for pid in FIRST_PAGE_ID..NUM_PAGES {
-            if bitmap_page.get_byte_value(pid)? == 0 {
-                self.set_page_type(pid, 1)?;
+        let mut pid = self.free_hint;
+        while pid < self.get_num_pages() {
+            let (byte, mask) = self.get_map_byte(pid)?;
+            if mask == 1 && byte == 0xff {
+                // skip fully used byte
+                pid += 8;
+                continue;
+            }
+            if byte & mask == 0 {
+                self.init_page(pid)?;
                 return Ok(pid);
             }
+            pid += 1;
+        }
+
+        // every page is used. extend file.
+        let mut pid = self.get_num_pages();
+        if self.is_map_page(pid) {
+            self.add_map_page(pid)?;
+            pid += 1;
         }
-        Err(Error::NoFreePage)
+        self.extend(pid + 1)?;
+        self.init_page(pid)?;
+        Ok(pid)
+    }
+
+    // mark page as used and write empty page with valid checksum
+    fn init_page(&mut self, page_id: PageId) -> Res<()> {
+        self.set_used(page_id, true)?;
+        self.free_hint = page_id + 1;
+        self.write_page_no_check(page_id, &Page::new(self.page_byte))
     }
 
     pub fn deallocate_page(&mut self, page_id: PageId) -> Res<()> {
-        self.set_page_type(page_id, 0)
+        if self.is_free_page(page_id)? {
+            return Err(Error::InvalidArg { msg: format!("DiskMgr::deallocate_page: page is not allocated. page_id={}", page_id) });
+        }
+        if page_id == SUPER_BLOCK_PAGE_ID || self.is_map_page(page_id) {
+            return Err(Error::InvalidArg { msg: format!("DiskMgr::deallocate_page: system page cannot be deallocated. page_id={}", page_id) });
+        }
+        self.set_used(page_id, false)?;
+        self.free_hint = self.free_hint.min(page_id);
+        Ok(())
+    }
+}
+
+impl PageStore for DiskMgr {
+    fn get_page_byte(&self) -> usize {
+        self.get_page_byte()
+    }
+
+    fn read_page(&mut self, page_id: PageId) -> Res<Page> {
+        self.read_page(page_id)
+    }
+
+    fn write_page(&mut self, page_id: PageId, page: &Page) -> Res<()> {
+        self.write_page(page_id, page)
+    }
+
+    fn allocate(&mut self) -> Res<PageId> {
+        self.allocate_page()
+    }
+
+    fn deallocate(&mut self, page_id: PageId) -> Res<()> {
+        self.deallocate_page(page_id)
+    }
+
+    fn sync(&mut self) -> Res<()> {
+        self.close_db()
+    }
+
+    fn get_catalog_root(&self) -> PageId {
+        self.get_catalog_root()
+    }
+
+    fn set_catalog_root(&mut self, page_id: PageId) -> Res<()> {
+        self.set_catalog_root(page_id)
     }
 }
 
@@ -156,6 +470,161 @@ pub fn run_diskmgr() -> Res<()> {
     Ok(())
 }
 
+pub fn run_diskmgr_grow() -> Res<()> {
+    let name = "nano-diskmgr-grow.db";
+    let num_pages = 100;
+    let mut pids = Vec::new();
+    {
+        let mut diskmgr = DiskMgr::open_db(name)?;
+        for i in 0..num_pages {
+            let pid = diskmgr.allocate_page()?;
+            let mut page = diskmgr.read_page(pid)?;
+            page.set_int_value(0, i)?;
+            diskmgr.write_page(pid, &page)?;
+            pids.push(pid);
+        }
+        assert_eq!(num_pages as usize + 2, diskmgr.get_num_pages());
+        diskmgr.close_db()?;
+    }
+
+    // reopen. contents must be kept.
+    let mut diskmgr = DiskMgr::open_db(name)?;
+    assert_eq!(num_pages as usize + 2, diskmgr.get_num_pages());
+    for (i, pid) in pids.iter().enumerate() {
+        let page = diskmgr.read_page(*pid)?;
+        assert_eq!(i as i32, page.get_int_value(0)?);
+    }
+
+    // freed page is reused before file grows
+    diskmgr.deallocate_page(pids[3])?;
+    assert_eq!(pids[3], diskmgr.allocate_page()?);
+    assert_eq!(num_pages as usize + 2, diskmgr.get_num_pages());
+    diskmgr.close_db()?;
+
+    std::fs::remove_file(name).unwrap();
+    Ok(())
+}
+
+pub fn run_diskmgr_map_pages() -> Res<()> {
+    let name = "nano-diskmgr-map.db";
+    let bits = (PAGE_BYTE - PAGE_HEADER_BYTE - MAP_BITS_START) * 8;
+    let num_pages = bits + 10;
+    {
+        let mut diskmgr = DiskMgr::open_db(name)?;
+        assert_eq!(bits, diskmgr.map_bits_per_page());
+        for _ in 0..num_pages {
+            let pid = diskmgr.allocate_page()?;
+            assert!(!diskmgr.is_map_page(pid));
+        }
+        assert_eq!(2, diskmgr.map_pages.len());
+        diskmgr.deallocate_page(bits + 5)?;
+        diskmgr.close_db()?;
+    }
+
+    let mut diskmgr = DiskMgr::open_db(name)?;
+    assert_eq!(2, diskmgr.map_pages.len());
+    assert!(!diskmgr.is_free_page(bits - 1)?);
+    assert!(!diskmgr.is_free_page(bits)?);
+    assert!(diskmgr.is_free_page(bits + 5)?);
+    assert!(diskmgr.deallocate_page(bits).is_err());
+    assert_eq!(bits + 5, diskmgr.allocate_page()?);
+    diskmgr.close_db()?;
+
+    std::fs::remove_file(name).unwrap();
+    Ok(())
+}
+
+pub fn run_diskmgr_super_block() -> Res<()> {
+    let name = "nano-diskmgr-super.db";
+    let pid = {
+        let mut diskmgr = DiskMgr::open_db(name)?;
+        assert_eq!(0, diskmgr.get_catalog_root());
+        let pid = diskmgr.allocate_page()?;
+        diskmgr.set_catalog_root(pid)?;
+        diskmgr.close_db()?;
+        pid
+    };
+    {
+        let diskmgr = DiskMgr::open_db(name)?;
+        assert_eq!(pid, diskmgr.get_catalog_root());
+        assert_eq!(pid + 1, diskmgr.get_num_pages());
+    }
+
+    // unknown format version
+    let mut data = std::fs::read(name).unwrap();
+    data[PAGE_HEADER_BYTE + SUPER_FORMAT_VERSION] = 99;
+    std::fs::write(name, &data).unwrap();
+    assert!(matches!(DiskMgr::open_db(name), Err(Error::InvalidFormat { .. })));
+
+    // foreign file
+    std::fs::write(name, "this is not a nanodb file").unwrap();
+    assert!(matches!(DiskMgr::open_db(name), Err(Error::InvalidFormat { .. })));
+    std::fs::write(name, vec![7; PAGE_BYTE * 3]).unwrap();
+    assert!(matches!(DiskMgr::open_db(name), Err(Error::InvalidFormat { .. })));
+
+    std::fs::remove_file(name).unwrap();
+    Ok(())
+}
+
+pub fn run_diskmgr_checksum() -> Res<()> {
+    let name = "nano-diskmgr-checksum.db";
+    let pid = {
+        let mut diskmgr = DiskMgr::open_db(name)?;
+        let pid = diskmgr.allocate_page()?;
+        let mut page = diskmgr.read_page(pid)?;
+        page.set_varchar_value(10, "hello")?;
+        diskmgr.write_page(pid, &page)?;
+        diskmgr.close_db()?;
+        pid
+    };
+
+    // simulate torn write
+    let mut data = std::fs::read(name).unwrap();
+    data[pid * PAGE_BYTE + PAGE_HEADER_BYTE + 10] = b'j';
+    std::fs::write(name, &data).unwrap();
+
+    let mut diskmgr = DiskMgr::open_db(name)?;
+    let e = diskmgr.read_page(pid);
+    assert!(matches!(e, Err(Error::Corruption { page_id }) if page_id == pid));
+
+    // rewriting the page repairs it
+    let mut page = Page::new(PAGE_BYTE);
+    page.set_varchar_value(10, "hello")?;
+    diskmgr.write_page(pid, &page)?;
+    assert_eq!("hello", diskmgr.read_page(pid)?.get_varchar_value(10, 5)?);
+    diskmgr.close_db()?;
+
+    std::fs::remove_file(name).unwrap();
+    Ok(())
+}
+
+pub fn run_diskmgr_page_byte() -> Res<()> {
+    let name = "nano-diskmgr-page-byte.db";
+    let page_byte = 8192;
+    let pid = {
+        let mut diskmgr = DiskMgr::open_db_with_page_byte(name, page_byte)?;
+        let pid = diskmgr.allocate_page()?;
+        let mut page = diskmgr.read_page(pid)?;
+        assert_eq!(page_byte - PAGE_HEADER_BYTE, page.get_size());
+        page.set_varchar_value(page_byte - 100, "hello")?;
+        diskmgr.write_page(pid, &page)?;
+        assert!(diskmgr.write_page(pid, &Page::new(PAGE_BYTE)).is_err());
+        diskmgr.close_db()?;
+        pid
+    };
+    assert_eq!((pid + 1) * page_byte, std::fs::metadata(name).unwrap().len() as usize);
+
+    // page size is taken from the file
+    let mut diskmgr = DiskMgr::open_db(name)?;
+    assert_eq!(page_byte, diskmgr.get_page_byte());
+    assert_eq!("hello", diskmgr.read_page(pid)?.get_varchar_value(page_byte - 100, 5)?);
+    assert!(matches!(DiskMgr::open_db_with_page_byte(name, 4096), Err(Error::InvalidFormat { .. })));
+    assert!(matches!(DiskMgr::open_db_with_page_byte(name, 1000), Err(Error::InvalidArg { .. })));
+
+    std::fs::remove_file(name).unwrap();
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -164,4 +633,29 @@ mod tests {
     fn test_diskmgr() {
         run_diskmgr().unwrap();
     }
+
+    #[test]
+    fn test_diskmgr_grow() {
+        run_diskmgr_grow().unwrap();
+    }
+
+    #[test]
+    fn test_diskmgr_map_pages() {
+        run_diskmgr_map_pages().unwrap();
+    }
+
+    #[test]
+    fn test_diskmgr_super_block() {
+        run_diskmgr_super_block().unwrap();
+    }
+
+    #[test]
+    fn test_diskmgr_checksum() {
+        run_diskmgr_checksum().unwrap();
+    }
+
+    #[test]
+    fn test_diskmgr_page_byte() {
+        run_diskmgr_page_byte().unwrap();
+    }
 }
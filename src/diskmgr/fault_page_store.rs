@@ -0,0 +1,381 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+
+use crate::types::*;
+use crate::page::Page;
+use super::PageStore;
+
+// probability of each fault per operation.
+#[derive(Clone, Debug, Default)]
+pub struct FaultConfig {
+    pub seed: u64,
+    pub write_error_rate: f64,
+    pub short_read_rate: f64,
+    pub sync_error_rate: f64,
+    // power is lost just before the n-th operation
+    pub power_loss_at: Option<usize>,
+}
+
+// page store which injects i/o errors deterministically from `seed`.
+// written pages and changes of allocation or catalog root are kept in memory
+// until `sync`, so that power loss can drop any subset of them like os page
+// cache does.
+pub struct FaultPageStore<S: PageStore> {
+    inner: S,
+    state: Arc<Mutex<FaultState>>,
+}
+
+struct FaultState {
+    config: FaultConfig,
+    rng: XorShift,
+    enabled: bool,
+    num_ops: usize,
+    num_injected: usize,
+    power_lost: bool,
+    pending: BTreeMap<PageId, Page>,
+    // unsynced changes of store metadata in the order they were made
+    pending_meta: Vec<MetaOp>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MetaOp {
+    // page id is taken from inner store at once so that it isn't handed out
+    // twice, and is given back to inner store if the allocation is lost.
+    Allocate(PageId),
+    Deallocate(PageId),
+    SetCatalogRoot(PageId),
+}
+
+// handle to control faults after the store is moved into BufMgr
+#[derive(Clone)]
+pub struct FaultController {
+    state: Arc<Mutex<FaultState>>,
+}
+
+struct XorShift {
+    x: u64,
+}
+
+impl XorShift {
+    fn new(seed: u64) -> XorShift {
+        XorShift { x: seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1 }
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        self.x ^= self.x << 13;
+        self.x ^= self.x >> 7;
+        self.x ^= self.x << 17;
+        (self.x >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn injected(msg: &str) -> Error {
+    Error::IoError(std::io::Error::other(format!("injected fault: {}", msg)))
+}
+
+impl FaultState {
+    // count operation and fail if power is lost
+    fn begin_op(&mut self) -> Res<()> {
+        if self.enabled && self.config.power_loss_at == Some(self.num_ops) {
+            self.lose_power();
+        }
+        self.num_ops += 1;
+        if self.power_lost {
+            return Err(injected("power lost"));
+        }
+        Ok(())
+    }
+
+    fn hit(&mut self, rate: f64) -> bool {
+        let hit = self.enabled && self.rng.next_f64() < rate;
+        if hit {
+            self.num_injected += 1;
+        }
+        hit
+    }
+
+    fn lose_power(&mut self) {
+        if self.power_lost {
+            return;
+        }
+        log::debug!("FaultPageStore: power lost. pending={} pending_meta={}",
+            self.pending.len(), self.pending_meta.len());
+        self.power_lost = true;
+        self.num_injected += 1;
+        // unsynced pages are partially written back. survivors are kept
+        // in `pending` and written to inner store on drop.
+        let pending = std::mem::take(&mut self.pending);
+        for (page_id, page) in pending {
+            if self.rng.next_f64() < 0.5 {
+                self.pending.insert(page_id, page);
+            }
+        }
+        // surviving changes of metadata are applied on drop as well.
+        // lost allocation is undone by deallocating the page in inner store.
+        let pending_meta = std::mem::take(&mut self.pending_meta);
+        let mut undone = BTreeSet::new();
+        for op in pending_meta {
+            let lost = self.rng.next_f64() < 0.5;
+            match op {
+                MetaOp::Allocate(page_id) => {
+                    if lost {
+                        undone.insert(page_id);
+                        self.pending.remove(&page_id);
+                        self.pending_meta.push(MetaOp::Deallocate(page_id));
+                    }
+                }
+                MetaOp::Deallocate(page_id) => {
+                    if !lost && !undone.contains(&page_id) {
+                        self.pending_meta.push(op);
+                    }
+                }
+                MetaOp::SetCatalogRoot(_) => {
+                    if !lost {
+                        self.pending_meta.push(op);
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_deallocated(&self, page_id: PageId) -> bool {
+        self.pending_meta.contains(&MetaOp::Deallocate(page_id))
+    }
+
+    fn check_allocated(&self, page_id: PageId) -> Res<()> {
+        if self.is_deallocated(page_id) {
+            return Err(Error::InvalidArg { msg: format!("not allocated page. page_id={}", page_id) });
+        }
+        Ok(())
+    }
+}
+
+impl<S: PageStore> FaultPageStore<S> {
+    pub fn new(inner: S, config: FaultConfig) -> FaultPageStore<S> {
+        let state = FaultState {
+            rng: XorShift::new(config.seed),
+            config,
+            enabled: true,
+            num_ops: 0,
+            num_injected: 0,
+            power_lost: false,
+            pending: BTreeMap::new(),
+            pending_meta: Vec::new(),
+        };
+        FaultPageStore { inner, state: Arc::new(Mutex::new(state)) }
+    }
+
+    pub fn controller(&self) -> FaultController {
+        FaultController { state: self.state.clone() }
+    }
+
+    fn write_back(inner: &mut S, state: &mut FaultState) -> Res<()> {
+        while let Some((page_id, page)) = state.pending.pop_first() {
+            inner.write_page(page_id, &page)?;
+        }
+        // pages are written first because deallocated page can't be written
+        for op in std::mem::take(&mut state.pending_meta) {
+            match op {
+                MetaOp::Allocate(_) => {}
+                MetaOp::Deallocate(page_id) => inner.deallocate(page_id)?,
+                MetaOp::SetCatalogRoot(page_id) => inner.set_catalog_root(page_id)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FaultController {
+    pub fn set_enabled(&self, enabled: bool) {
+        self.state.lock().unwrap().enabled = enabled;
+    }
+
+    // simulate power loss now. every following operation fails.
+    pub fn lose_power(&self) {
+        self.state.lock().unwrap().lose_power();
+    }
+
+    pub fn get_num_injected(&self) -> usize {
+        self.state.lock().unwrap().num_injected
+    }
+}
+
+impl<S: PageStore> PageStore for FaultPageStore<S> {
+    fn get_page_byte(&self) -> usize {
+        self.inner.get_page_byte()
+    }
+
+    fn read_page(&mut self, page_id: PageId) -> Res<Page> {
+        let mut state = self.state.lock().unwrap();
+        state.begin_op()?;
+        let rate = state.config.short_read_rate;
+        if state.hit(rate) {
+            return Err(Error::IoError(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof, "injected fault: short read")));
+        }
+        state.check_allocated(page_id)?;
+        match state.pending.get(&page_id) {
+            Some(page) => Ok(page.clone()),
+            None => self.inner.read_page(page_id),
+        }
+    }
+
+    fn write_page(&mut self, page_id: PageId, page: &Page) -> Res<()> {
+        let mut state = self.state.lock().unwrap();
+        state.begin_op()?;
+        let rate = state.config.write_error_rate;
+        if state.hit(rate) {
+            return Err(injected("write failed"));
+        }
+        // check allocation and page size on write
+        state.check_allocated(page_id)?;
+        self.inner.read_page(page_id)?;
+        if page.get_page_byte() != self.inner.get_page_byte() {
+            return Err(Error::InvalidArg { msg: "FaultPageStore::write_page: page size mismatch".to_string() });
+        }
+        state.pending.insert(page_id, page.clone());
+        Ok(())
+    }
+
+    fn allocate(&mut self) -> Res<PageId> {
+        let mut state = self.state.lock().unwrap();
+        state.begin_op()?;
+        let rate = state.config.write_error_rate;
+        if state.hit(rate) {
+            return Err(injected("allocate failed"));
+        }
+        let page_id = self.inner.allocate()?;
+        state.pending.remove(&page_id);
+        state.pending_meta.push(MetaOp::Allocate(page_id));
+        Ok(page_id)
+    }
+
+    fn deallocate(&mut self, page_id: PageId) -> Res<()> {
+        let mut state = self.state.lock().unwrap();
+        state.begin_op()?;
+        let rate = state.config.write_error_rate;
+        if state.hit(rate) {
+            return Err(injected("deallocate failed"));
+        }
+        state.check_allocated(page_id)?;
+        self.inner.read_page(page_id)?;
+        state.pending.remove(&page_id);
+        state.pending_meta.push(MetaOp::Deallocate(page_id));
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Res<()> {
+        let mut state = self.state.lock().unwrap();
+        state.begin_op()?;
+        let rate = state.config.sync_error_rate;
+        if state.hit(rate) {
+            return Err(injected("sync failed"));
+        }
+        Self::write_back(&mut self.inner, &mut state)?;
+        self.inner.sync()
+    }
+
+    fn get_catalog_root(&self) -> PageId {
+        let state = self.state.lock().unwrap();
+        let pending = state.pending_meta.iter().rev().find_map(|op| match op {
+            MetaOp::SetCatalogRoot(page_id) => Some(*page_id),
+            _ => None,
+        });
+        pending.unwrap_or_else(|| self.inner.get_catalog_root())
+    }
+
+    fn set_catalog_root(&mut self, page_id: PageId) -> Res<()> {
+        let mut state = self.state.lock().unwrap();
+        state.begin_op()?;
+        let rate = state.config.write_error_rate;
+        if state.hit(rate) {
+            return Err(injected("set catalog root failed"));
+        }
+        state.pending_meta.push(MetaOp::SetCatalogRoot(page_id));
+        Ok(())
+    }
+}
+
+impl<S: PageStore> Drop for FaultPageStore<S> {
+    fn drop(&mut self) {
+        // without power loss, OS writes back unsynced pages eventually
+        let mut state = self.state.lock().unwrap();
+        let _ = Self::write_back(&mut self.inner, &mut state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diskmgr::MemPageStore;
+    use crate::page::PAGE_BYTE;
+    use super::*;
+
+    #[test]
+    fn test_fault_page_store() -> Res<()> {
+        let config = FaultConfig { seed: 1, write_error_rate: 0.5, ..FaultConfig::default() };
+        let mut store = FaultPageStore::new(MemPageStore::new(PAGE_BYTE), config);
+        let faults = store.controller();
+        faults.set_enabled(false);
+        let pid = store.allocate()?;
+        faults.set_enabled(true);
+
+        let mut page = Page::new(PAGE_BYTE);
+        page.set_int_value(0, 7)?;
+        let mut num_errors = 0;
+        for _ in 0..20 {
+            match store.write_page(pid, &page) {
+                Ok(_) => {}
+                Err(Error::IoError(_)) => num_errors += 1,
+                Err(e) => return Err(e),
+            }
+        }
+        assert!(num_errors > 0 && num_errors < 20);
+        assert_eq!(num_errors, faults.get_num_injected());
+
+        // synced page survives power loss
+        faults.set_enabled(false);
+        store.sync()?;
+        page.set_int_value(0, 8)?;
+        store.write_page(pid, &page)?;
+        assert_eq!(8, store.read_page(pid)?.get_int_value(0)?);
+        assert_eq!(7, store.inner.read_page(pid)?.get_int_value(0)?);
+        faults.lose_power();
+        assert!(matches!(store.read_page(pid), Err(Error::IoError(_))));
+        assert!(matches!(store.sync(), Err(Error::IoError(_))));
+        assert_eq!(num_errors + 1, faults.get_num_injected());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fault_page_store_metadata() -> Res<()> {
+        // unsynced allocation, deallocation and catalog root may be lost
+        let mut kept = BTreeSet::new();
+        for seed in 0..16 {
+            let config = FaultConfig { seed, ..FaultConfig::default() };
+            let mut store = FaultPageStore::new(MemPageStore::new(PAGE_BYTE), config);
+            let faults = store.controller();
+            let old = store.allocate()?;
+            store.set_catalog_root(old)?;
+            store.sync()?;
+
+            let new = store.allocate()?;
+            store.deallocate(old)?;
+            assert!(store.read_page(old).is_err());
+            store.set_catalog_root(new)?;
+            assert_eq!(new, store.get_catalog_root());
+            assert_eq!(old, store.inner.get_catalog_root());
+            assert!(store.inner.read_page(old).is_ok());
+
+            // write back survivors as drop does
+            faults.lose_power();
+            FaultPageStore::write_back(&mut store.inner, &mut store.state.lock().unwrap())?;
+            let root = store.inner.get_catalog_root();
+            assert!(root == old || root == new);
+            kept.insert((root == new, store.inner.read_page(new).is_ok(), store.inner.read_page(old).is_ok()));
+        }
+        assert!(kept.iter().any(|(root, _, _)| *root) && kept.iter().any(|(root, _, _)| !*root));
+        assert!(kept.iter().any(|(_, new, _)| *new) && kept.iter().any(|(_, new, _)| !*new));
+        assert!(kept.iter().any(|(_, _, old)| *old) && kept.iter().any(|(_, _, old)| !*old));
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use crate::types::*;
+use crate::page::Page;
+use super::PageStore;
+
+// page store on memory. contents are lost when dropped.
+pub struct MemPageStore {
+    page_byte: usize,
+    pages: Vec<Option<Page>>,
+    free_page_ids: Vec<PageId>,
+    catalog_root_page_id: PageId,
+}
+
+impl MemPageStore {
+    pub fn new(page_byte: usize) -> MemPageStore {
+        MemPageStore {
+            page_byte,
+            pages: vec![None], // page 0 is reserved as invalid page id
+            free_page_ids: Vec::new(),
+            catalog_root_page_id: 0,
+        }
+    }
+
+    fn get_page_mut(&mut self, page_id: PageId) -> Res<&mut Page> {
+        match self.pages.get_mut(page_id) {
+            Some(Some(page)) => Ok(page),
+            _ => Err(Error::InvalidArg { msg: "not allocated page".to_string() }),
+        }
+    }
+}
+
+impl PageStore for MemPageStore {
+    fn get_page_byte(&self) -> usize {
+        self.page_byte
+    }
+
+    fn read_page(&mut self, page_id: PageId) -> Res<Page> {
+        self.get_page_mut(page_id).map(|page| page.clone())
+    }
+
+    fn write_page(&mut self, page_id: PageId, page: &Page) -> Res<()> {
+        if page.get_page_byte() != self.page_byte {
+            return Err(Error::InvalidArg {
+                msg: format!("MemPageStore::write_page: page size {} differs from {}", page.get_page_byte(), self.page_byte)
+            });
+        }
+        *self.get_page_mut(page_id)? = page.clone();
+        Ok(())
+    }
+
+    fn allocate(&mut self) -> Res<PageId> {
+        let page = Some(Page::new(self.page_byte));
+        match self.free_page_ids.pop() {
+            Some(page_id) => {
+                self.pages[page_id] = page;
+                Ok(page_id)
+            }
+            None => {
+                self.pages.push(page);
+                Ok(self.pages.len() - 1)
+            }
+        }
+    }
+
+    fn deallocate(&mut self, page_id: PageId) -> Res<()> {
+        self.get_page_mut(page_id)?;
+        self.pages[page_id] = None;
+        self.free_page_ids.push(page_id);
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Res<()> {
+        Ok(())
+    }
+
+    fn get_catalog_root(&self) -> PageId {
+        self.catalog_root_page_id
+    }
+
+    fn set_catalog_root(&mut self, page_id: PageId) -> Res<()> {
+        self.catalog_root_page_id = page_id;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::page::PAGE_BYTE;
+    use super::*;
+
+    #[test]
+    fn test_mem_page_store() -> Res<()> {
+        let mut store = MemPageStore::new(PAGE_BYTE);
+        let pid0 = store.allocate()?;
+        let pid1 = store.allocate()?;
+        assert!(pid0 > 0);
+        let mut page = store.read_page(pid0)?;
+        page.set_varchar_value(10, "hello")?;
+        store.write_page(pid0, &page)?;
+        assert_eq!("hello", store.read_page(pid0)?.get_varchar_value(10, 5)?);
+
+        store.deallocate(pid0)?;
+        assert!(store.read_page(pid0).is_err());
+        assert!(store.deallocate(pid0).is_err());
+        // freed page is reused and cleared
+        assert_eq!(pid0, store.allocate()?);
+        assert_eq!(0, store.read_page(pid0)?.get_byte_value(10)?);
+        assert!(store.read_page(pid1).is_ok());
+        assert_eq!(3, store.pages.len());
+        Ok(())
+    }
+}
@@ -6,20 +6,39 @@ pub use heap_file::*;
 pub use raw_file_scan::*;
 pub use hfilemgr::*;
 
-use crate::page::PAGE_BYTE;
+use std::ops::{Deref, DerefMut};
 
 use super::types::{Res, PageId, EntryNo, Error};
 use super::page::Page;
 use super::diskmgr::DiskMgr;
 
+const HEADER_NEXT_PAGE_ID: usize = 0;
 const HEADER_START_FILE_ENTRY: usize = 10;
 const HEADER_NAME_BYTE: usize = 20;
 const HEADER_FILE_ENTRY_BYTE: usize = 4 + 4 + HEADER_NAME_BYTE + 2;
 
-pub const PAGE_RECORD_BYTE: usize = 128;
 const PAGE_NEXT_PAGE_ID: usize = 0;
 const PAGE_PREV_PAGE_ID: usize = 4;
-const PAGE_RECORD_START: usize = 10;
+const PAGE_NUM_SLOTS: usize = 8;
+const PAGE_FREE_END: usize = 12;
+const PAGE_SLOT_START: usize = 16;
+const PAGE_SLOT_BYTE: usize = 8;
+const PAGE_FORWARD_BYTE: usize = 8;
+const SLOT_LENGTH_MASK: usize = 0xffff;
+const SLOT_FORWARD: usize = 1 << 16;
+const SLOT_MOVED: usize = 1 << 17;
+const SLOT_OVERFLOW: usize = 1 << 18;
+
+const FSM_NEXT_PAGE_ID: usize = 0;
+const FSM_NUM_ENTRIES: usize = 4;
+const FSM_MAX_CATEGORY: usize = 8;
+const FSM_ENTRY_START: usize = 9;
+const FSM_ENTRY_BYTE: usize = 5;
+const FSM_NUM_CATEGORIES: usize = 256;
+
+const OVERFLOW_NEXT_PAGE_ID: usize = 0;
+const OVERFLOW_LENGTH: usize = 4;
+const OVERFLOW_DATA_START: usize = 8;
 
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub struct SlotNo {
@@ -51,91 +70,93 @@ impl RecordId {
     }
 }
 
-const HEADER_MAX_ENTRY: usize = 10;
-
 // FIXME: HeaderPage can be replaced as ordinary Relation table.
-pub struct HeaderPage<'a> { page: &'a mut Page }
-
-impl<'a> HeaderPage<'a> {
-    pub fn new(page: &'a mut Page) -> Self {
+// page of file directory. directory pages are chained from the catalog root.
+// format
+// next_page_id: 4
+// (reserved): 6
+// entry_0: head_page_id 4, fsm_page_id 4, name HEADER_NAME_BYTE, (reserved) 2
+// ...
+// P is &Page for read-only access, or &mut Page for modification.
+pub struct HeaderPage<P> { page: P }
+
+impl<P: Deref<Target = Page>> HeaderPage<P> {
+    pub fn new(page: P) -> Self {
         Self { page }
     }
 
-    fn new_entry(&mut self) -> Res<EntryNo> {
-        let mut eno = 0;
-        while eno < HEADER_MAX_ENTRY {
-            let page_id = self.get_head_free_page_id(EntryNo::new(eno))?;
-            if page_id == 0 {
-                return Ok(EntryNo::new(eno));
+    // number of entries depends on page size
+    fn max_entry(&self) -> usize {
+        (self.page.get_size() - HEADER_START_FILE_ENTRY) / HEADER_FILE_ENTRY_BYTE
+    }
+
+    pub fn get_next_page_id(&self) -> Res<PageId> {
+        let page_id = self.page.get_int_value(HEADER_NEXT_PAGE_ID)? as PageId;
+        Ok(page_id)
+    }
+
+    // index of unused entry
+    fn new_entry(&self) -> Res<Option<usize>> {
+        for i in 0..self.max_entry() {
+            if self.get_head_free_page_id_at(i)? == 0 {
+                return Ok(Some(i));
             }
-            eno += 1;
         }
-        Err(Error::InvalidArg { msg: "FileMgr::create_file: too much entry".to_string() })
+        Ok(None)
+    }
+
+    fn pos_entry(&self, i: usize) -> usize {
+        HEADER_START_FILE_ENTRY + i * HEADER_FILE_ENTRY_BYTE
     }
 
     fn pos_head_free_page_id(&self, entry_no: EntryNo) -> usize {
-        HEADER_START_FILE_ENTRY + entry_no.value * HEADER_FILE_ENTRY_BYTE        
+        self.pos_entry(entry_no.value)
     }
 
-    pub fn set_head_free_page_id(&mut self, entry_no: EntryNo, page_id: PageId) -> Res<()> {
-        let position = self.pos_head_free_page_id(entry_no);
-        self.page.set_int_value(position, page_id as i32)?; // for free-page
-        Ok(())
+    pub fn get_head_free_page_id(&self, entry_no: EntryNo) -> Res<PageId> {
+        self.get_head_free_page_id_at(entry_no.value)
     }
 
-    pub fn get_head_free_page_id(&mut self, entry_no: EntryNo) -> Res<PageId> {
-        let position = self.pos_head_free_page_id(entry_no);
-        let page_id = self.page.get_int_value(position)? as PageId;
+    fn get_head_free_page_id_at(&self, i: usize) -> Res<PageId> {
+        let page_id = self.page.get_int_value(self.pos_entry(i))? as PageId;
         Ok(page_id)
     }
 
-    // FIXME: full_page_id is unnecessary
-    fn pos_head_full_page_id(&self, entry_no: EntryNo) -> usize {
+    // first page of free-space map. 0 means the map is not built yet.
+    fn pos_fsm_page_id(&self, entry_no: EntryNo) -> usize {
         self.pos_head_free_page_id(entry_no) + 4
     }
 
-    pub fn set_head_full_page_id(&mut self, entry_no: EntryNo, page_id: PageId) -> Res<()> {
-        let position = self.pos_head_full_page_id(entry_no);
-        self.page.set_int_value(position, page_id as i32)?; // for free-page
-        Ok(())
+    pub fn get_fsm_page_id(&self, entry_no: EntryNo) -> Res<PageId> {
+        let position = self.pos_fsm_page_id(entry_no);
+        let page_id = self.page.get_int_value(position)? as PageId;
+        Ok(page_id)
     }
 
-    // pub fn get_head_full_page_id(&mut self, entry_no: EntryNo) -> Res<PageId> {
-    //     let position = self.pos_head_full_page_id(entry_no);
-    //     let page_id = self.page.get_int_value(position)? as PageId;
-    //     Ok(page_id)
-    // }    
-
     fn pos_name(&self, entry_no: EntryNo) -> usize {
         self.pos_head_free_page_id(entry_no) + 8
     }
 
-    pub fn set_name(&mut self, entry_no: EntryNo, name: &str) -> Res<()> {
-        if name.len() > HEADER_NAME_BYTE {
-            return Err(Error::InvalidArg{ msg: format!("HeaderPage::set_name : name length must be less than {}", HEADER_NAME_BYTE)});
-        }
-        let position = self.pos_name(entry_no);
-        self.page.set_varchar_value(position, &name)?;
-        Ok(())
+    pub fn get_name(&self, entry_no: EntryNo) -> Res<String> {
+        self.get_name_at(entry_no.value)
     }
 
-    pub fn get_name(&self, entry_no: EntryNo) -> Res<Option<String>> {
-        let position = self.pos_name(entry_no);
+    // name is padded with zero
+    fn get_name_at(&self, i: usize) -> Res<String> {
+        let position = self.pos_entry(i) + 8;
         let a = self.page.get_varchar_value(position, HEADER_NAME_BYTE)?;
-        Ok(Some(a))
+        Ok(a.trim_end_matches('\0').to_string())
     }
 
-    pub fn find(&mut self, name: &str) -> Res<Option<EntryNo>> {
+    // index of entry whose name is exactly `name`
+    pub fn find(&self, name: &str) -> Res<Option<usize>> {
         if name.len() > HEADER_NAME_BYTE {
-            return Err(Error::InvalidArg{ msg: format!("HeaderPage::find : name length must be less than {}", HEADER_NAME_BYTE)});
-        }
-        for eno in 0..HEADER_MAX_ENTRY {
-            let entry_no = EntryNo::new(eno);
-            if let Some(eno_name) = self.get_name(entry_no)? {
-                let page_id = self.get_head_free_page_id(entry_no)?;
-                if name == &eno_name[..name.len()] && page_id > 0 {
-                    return Ok(Some(entry_no))
-                }
+            // no entry can hold such name
+            return Ok(None);
+        }
+        for i in 0..self.max_entry() {
+            if self.get_head_free_page_id_at(i)? > 0 && self.get_name_at(i)? == name {
+                return Ok(Some(i))
             }
         }
         Ok(None)
@@ -147,167 +168,687 @@ impl<'a> HeaderPage<'a> {
     // }
 }
 
-pub struct RecordPage<'a> { page: &'a mut Page}
-
-impl<'a> RecordPage<'a> {
-    pub fn new(page: &'a mut Page) -> Self {
-        Self { page }
+impl<P: DerefMut<Target = Page>> HeaderPage<P> {
+    pub fn set_next_page_id(&mut self, page_id: PageId) -> Res<()> {
+        self.page.set_int_value(HEADER_NEXT_PAGE_ID, page_id as i32)?;
+        Ok(())
     }
 
-    pub fn set_num_slots(&mut self, num_slots: usize) -> Res<()> {
-        self.page.set_int_value(PAGE_BYTE - 4, num_slots as i32)?;
+    pub fn set_head_free_page_id(&mut self, entry_no: EntryNo, page_id: PageId) -> Res<()> {
+        let position = self.pos_head_free_page_id(entry_no);
+        self.page.set_int_value(position, page_id as i32)?; // for free-page
         Ok(())
     }
 
-    pub fn get_num_slots(&mut self) -> Res<usize> {
-        let num_slots = self.page.get_int_value(PAGE_BYTE - 4)? as usize;
-        Ok(num_slots)
+    pub fn set_fsm_page_id(&mut self, entry_no: EntryNo, page_id: PageId) -> Res<()> {
+        let position = self.pos_fsm_page_id(entry_no);
+        self.page.set_int_value(position, page_id as i32)?;
+        Ok(())
     }
 
-    pub fn set_next_page_id(&mut self, page_id: PageId) -> Res<()> {
-        self.page.set_int_value(PAGE_NEXT_PAGE_ID, page_id as i32)?;
+    pub fn set_name(&mut self, entry_no: EntryNo, name: &str) -> Res<()> {
+        if name.len() > HEADER_NAME_BYTE {
+            return Err(Error::InvalidArg{ msg: format!("HeaderPage::set_name : name length must be less than {}", HEADER_NAME_BYTE)});
+        }
+        // pad with zero so that longer old name doesn't remain
+        let mut buf = [0; HEADER_NAME_BYTE];
+        buf[..name.len()].copy_from_slice(name.as_bytes());
+        let position = self.pos_name(entry_no);
+        for (i, x) in buf.iter().enumerate() {
+            self.page.set_byte_value(position + i, *x)?;
+        }
         Ok(())
     }
+}
+
+// slotted page. slot directory grows from the head and tuple data grows from the tail.
+// format
+// next_page_id: 4
+// prev_page_id: 4
+// num_slots: 4
+// free_end: 4   (start of tuple data. 0 means empty page)
+// slot_0: offset 4, length 4   (offset 0 means free slot. flags in upper bits of length)
+// ...
+// slot_n-1
+// (free space)
+// tuple data
+//
+// each tuple occupies at least PAGE_FORWARD_BYTE so that it can be replaced
+// by a forwarding pointer in place. record larger than a page is stored in chain
+// of overflow pages and its slot keeps (length 4, first_page_id 4).
+pub struct RecordPage<P> { page: P }
+
+// space occupied by tuple of given length
+fn tuple_space(length: usize) -> usize {
+    length.max(PAGE_FORWARD_BYTE)
+}
+
+impl<P: Deref<Target = Page>> RecordPage<P> {
+    pub fn new(page: P) -> Self {
+        Self { page }
+    }
+
+    // largest record which can be stored in page of given size
+    pub fn max_record_len(page_size: usize) -> usize {
+        page_size - PAGE_SLOT_START - PAGE_SLOT_BYTE
+    }
+
+    pub fn get_num_slots(&self) -> Res<usize> {
+        let num_slots = self.page.get_int_value(PAGE_NUM_SLOTS)? as usize;
+        Ok(num_slots)
+    }
 
-    pub fn get_next_page_id(&mut self) -> Res<PageId> {
+    pub fn get_next_page_id(&self) -> Res<PageId> {
         let page_id = self.page.get_int_value(PAGE_NEXT_PAGE_ID)? as PageId;
         Ok(page_id)
     }
 
-    pub fn set_prev_page_id(&mut self, page_id: PageId) -> Res<()> {
-        self.page.set_int_value(PAGE_PREV_PAGE_ID, page_id as i32)?;
-        Ok(())
+    pub fn get_prev_page_id(&self) -> Res<PageId> {
+        let page_id = self.page.get_int_value(PAGE_PREV_PAGE_ID)? as PageId;
+        Ok(page_id)
     }
 
-    // pub fn get_prev_page_id(&mut self) -> Res<PageId> {
-    //     let page_id = self.page.get_int_value(PAGE_NEXT_PAGE_ID)? as PageId;
-    //     Ok(page_id)
-    // }
+    fn get_free_end(&self) -> Res<usize> {
+        let free_end = self.page.get_int_value(PAGE_FREE_END)? as usize;
+        Ok(if free_end == 0 { self.page.get_size() } else { free_end })
+    }
 
-    pub fn capasity(&self) -> usize {
-        // format
-        // next_page_id: 4
-        // prev_page_id: 4
-        // slot_1: PAGE_RECORD_BYTE
-        // ...
-        // slot_n: PAGE_RECORD_BYTE
-        // flag_1: 1,
-        // ...
-        // flag_n: 1,
-        // num_slots: 4
-        // total: 8 + PAGE_RECORD_BYTE * n + 1 * n + 4
-        (PAGE_BYTE - 12) / (1 + PAGE_RECORD_BYTE)
-    }
-
-    pub fn set_slot_bit(&mut self, slot_no: SlotNo, bit: u8) -> Res<()> {
-        let num_slots = self.get_num_slots()?;
-        if slot_no.value >= num_slots {
-            return Err(Error::InvalidArg{ msg: format!("RecordPage::set_slot : slot_no must be less than {}", num_slots)});
-        }
-        let position = PAGE_BYTE - 5 - slot_no.value;
-        self.page.set_byte_value(position, bit)?;
-        Ok(())
+    fn pos_slot(&self, slot_no: SlotNo) -> usize {
+        PAGE_SLOT_START + PAGE_SLOT_BYTE * slot_no.value
     }
 
-    fn get_slot_bit(&mut self, slot_no: SlotNo) -> Res<u8> {
+    // (offset, length, flags) of tuple data
+    fn get_slot_entry(&self, slot_no: SlotNo) -> Res<(usize, usize, usize)> {
         self.check_slot_no(slot_no)?;
-        let position = PAGE_BYTE - 5 - slot_no.value;
-        self.page.get_byte_value(position)
+        let position = self.pos_slot(slot_no);
+        let offset = self.page.get_int_value(position)? as usize;
+        let length = self.page.get_int_value(position + 4)? as usize;
+        Ok((offset, length & SLOT_LENGTH_MASK, length & !SLOT_LENGTH_MASK))
+    }
+
+    pub fn is_free_slot(&self, slot_no: SlotNo) -> Res<bool> {
+        let (offset, _, _) = self.get_slot_entry(slot_no)?;
+        Ok(offset == 0)
+    }
+
+    // record relocated from other slot. scans skip it and reach it through the forwarding slot.
+    pub fn is_moved_slot(&self, slot_no: SlotNo) -> Res<bool> {
+        let (offset, _, flags) = self.get_slot_entry(slot_no)?;
+        Ok(offset > 0 && flags & SLOT_MOVED != 0)
+    }
+
+    // location of relocated record if slot holds forwarding pointer
+    pub fn get_forward(&self, slot_no: SlotNo) -> Res<Option<RecordId>> {
+        let (offset, _, flags) = self.get_slot_entry(slot_no)?;
+        if offset == 0 || flags & SLOT_FORWARD == 0 {
+            return Ok(None);
+        }
+        let page_id = self.page.get_int_value(offset)? as PageId;
+        let slot_no = self.page.get_int_value(offset + 4)? as usize;
+        Ok(Some(RecordId::new(page_id, SlotNo::new(slot_no))))
+    }
+
+    // (length, first_page_id) if record is stored in overflow pages
+    pub fn get_overflow(&self, slot_no: SlotNo) -> Res<Option<(usize, PageId)>> {
+        let (offset, _, flags) = self.get_slot_entry(slot_no)?;
+        if offset == 0 || flags & SLOT_OVERFLOW == 0 {
+            return Ok(None);
+        }
+        let length = self.page.get_int_value(offset)? as usize;
+        let page_id = self.page.get_int_value(offset + 4)? as PageId;
+        Ok(Some((length, page_id)))
+    }
+
+    pub fn get_slot(&self, slot_no: SlotNo) -> Res<&[u8]> {
+        let (offset, length, flags) = self.get_slot_entry(slot_no)?;
+        if offset == 0 {
+            return Err(Error::InvalidArg { 
+                msg: format!(
+                    "RecordPage::get_slot: try to get free slot. slot_no={}",
+                    slot_no.value
+                ) })
+        }
+        if flags & SLOT_FORWARD != 0 {
+            return Err(Error::InvalidArg { 
+                msg: format!(
+                    "RecordPage::get_slot: record is forwarded. slot_no={}",
+                    slot_no.value
+                ) })
+        }
+        if flags & SLOT_OVERFLOW != 0 {
+            return Err(Error::InvalidArg { 
+                msg: format!(
+                    "RecordPage::get_slot: record is stored in overflow pages. slot_no={}",
+                    slot_no.value
+                ) })
+        }
+        Ok(&self.page.get_data()[offset..offset+length])
+    }
+
+    // first pages of overflow chains referred from this page
+    pub fn get_overflow_page_ids(&self) -> Res<Vec<PageId>> {
+        let mut page_ids = Vec::new();
+        for i in 0..self.get_num_slots()? {
+            if let Some((_, page_id)) = self.get_overflow(SlotNo::new(i))? {
+                page_ids.push(page_id);
+            }
+        }
+        Ok(page_ids)
+    }
+
+    fn find_free_slot(&self) -> Res<Option<SlotNo>> {
+        for i in 0..self.get_num_slots()? {
+            let slot_no = SlotNo::new(i);
+            if self.is_free_slot(slot_no)? {
+                return Ok(Some(slot_no));
+            }
+        }
+        Ok(None)
     }
 
-    pub fn is_free_slot(&mut self, slot_no: SlotNo) -> Res<bool> {
+    // bytes available for new records including fragmented space
+    pub fn get_free_space(&self) -> Res<usize> {
         let num_slots = self.get_num_slots()?;
-        if slot_no.value >= num_slots {
-            return Err(Error::InvalidArg{ msg: format!("RecordPage::get_slot : slot_no must be less than {}", num_slots)});
+        let mut used = PAGE_SLOT_START + PAGE_SLOT_BYTE * num_slots;
+        for i in 0..num_slots {
+            let (offset, length, _) = self.get_slot_entry(SlotNo::new(i))?;
+            if offset > 0 {
+                used += tuple_space(length);
+            }
         }
-        let position = PAGE_BYTE - 5 - slot_no.value;
-        let bit = self.page.get_byte_value(position)?;
-        Ok(bit == 0)
+        Ok(self.page.get_size() - used)
     }
 
-    fn pos_slot(&self, slot_no: SlotNo) -> usize {
-        PAGE_RECORD_START + PAGE_RECORD_BYTE * slot_no.value
+    // bytes occupied by slot directory and tuple area including dead tuples
+    pub fn get_used_space(&self) -> Res<usize> {
+        let dir_end = self.pos_slot(SlotNo::new(self.get_num_slots()?));
+        Ok(self.page.get_size() - (self.get_free_end()? - dir_end))
+    }
+
+    // tuple data and flags of slot as stored, so that it can be moved to other page
+    pub fn get_raw_slot(&self, slot_no: SlotNo) -> Res<(Vec<u8>, usize)> {
+        let (offset, length, flags) = self.get_slot_entry(slot_no)?;
+        if offset == 0 {
+            return Err(Error::InvalidArg { 
+                msg: format!("RecordPage::get_raw_slot: try to get free slot. slot_no={}", slot_no.value) })
+        }
+        Ok((self.page.get_data()[offset..offset+length].to_vec(), flags))
+    }
+
+    // free space required to insert record of given length
+    pub fn required_space(len: usize) -> usize {
+        tuple_space(len) + PAGE_SLOT_BYTE
     }
 
-    pub fn set_slot(&mut self, slot_no: SlotNo, data: [u8; PAGE_RECORD_BYTE]) -> Res<()> {
+    pub fn can_insert(&self, len: usize) -> Res<bool> {
+        let slot_byte = if self.find_free_slot()?.is_some() { 0 } else { PAGE_SLOT_BYTE };
+        Ok(tuple_space(len) + slot_byte <= self.get_free_space()?)
+    }
+
+    fn check_slot_no(&self, slot_no: SlotNo) -> Res<()> {
         let num_slots = self.get_num_slots()?;
         if slot_no.value >= num_slots {
-            return Err(Error::InvalidArg{ msg: format!("RecordPage::set_slot : slot_no must be less than {}", num_slots)});
+            return Err(Error::InvalidArg{ msg: format!("RecordPage::check_slot_no : slot_no must be less than {}", num_slots)});
         }
+        Ok(())
+    }
+}
+
+impl<P: DerefMut<Target = Page>> RecordPage<P> {
+    fn set_num_slots(&mut self, num_slots: usize) -> Res<()> {
+        self.page.set_int_value(PAGE_NUM_SLOTS, num_slots as i32)?;
+        Ok(())
+    }
+
+    fn set_free_end(&mut self, free_end: usize) -> Res<()> {
+        self.page.set_int_value(PAGE_FREE_END, free_end as i32)?;
+        Ok(())
+    }
+
+    pub fn set_next_page_id(&mut self, page_id: PageId) -> Res<()> {
+        self.page.set_int_value(PAGE_NEXT_PAGE_ID, page_id as i32)?;
+        Ok(())
+    }
+
+    pub fn set_prev_page_id(&mut self, page_id: PageId) -> Res<()> {
+        self.page.set_int_value(PAGE_PREV_PAGE_ID, page_id as i32)?;
+        Ok(())
+    }
+
+    fn set_slot_entry(&mut self, slot_no: SlotNo, offset: usize, length: usize, flags: usize) -> Res<()> {
+        self.check_slot_no(slot_no)?;
         let position = self.pos_slot(slot_no);
-        for i in 0..PAGE_RECORD_BYTE {
-            self.page.set_byte_value(position + i, data[i])?;
-        }
-        self.set_slot_bit(slot_no, 1)?;
+        self.page.set_int_value(position, offset as i32)?;
+        self.page.set_int_value(position + 4, (length | flags) as i32)?;
         Ok(())
     }
 
-    pub fn add_slot(&mut self, data: [u8; PAGE_RECORD_BYTE]) -> Res<SlotNo> {
-        let num_slots = self.get_num_slots()?;
-        if num_slots >= self.capasity() {
-            return Err(Error::InvalidArg{ msg: format!("RecordPage::add_slot : num_slots must be less than {}", self.capasity())});
+    pub fn free_slot(&mut self, slot_no: SlotNo) -> Res<()> {
+        self.set_slot_entry(slot_no, 0, 0, 0)
+    }
+
+    pub fn add_slot(&mut self, data: &[u8]) -> Res<SlotNo> {
+        if !self.can_insert(data.len())? {
+            return Err(Error::InvalidArg{ msg: format!("RecordPage::add_slot : no space for record of {} bytes", data.len())});
         }
-        let slot_no = SlotNo::new(num_slots);
+        let slot_no = match self.find_free_slot()? {
+            Some(slot_no) => slot_no,
+            None => {
+                let num_slots = self.get_num_slots()?;
+                // new directory entry must not overlap tuple data
+                if self.get_free_end()? < self.pos_slot(SlotNo::new(num_slots + 1)) + tuple_space(data.len()) {
+                    self.compact()?;
+                }
+                self.set_num_slots(num_slots + 1)?;
+                self.set_slot_entry(SlotNo::new(num_slots), 0, 0, 0)?;
+                SlotNo::new(num_slots)
+            }
+        };
+        self.write_tuple(slot_no, data, 0)?;
+        Ok(slot_no)
+    }
 
-        self.set_num_slots(num_slots + 1)?;
-        self.set_slot(slot_no, data)?;
-        self.set_slot_bit(slot_no, 1)?;
+    // add tuple taken from get_raw_slot keeping its flags
+    pub fn add_raw_slot(&mut self, data: &[u8], flags: usize) -> Res<SlotNo> {
+        let slot_no = self.add_slot(data)?;
+        let (offset, length, _) = self.get_slot_entry(slot_no)?;
+        self.set_slot_entry(slot_no, offset, length, flags)?;
         Ok(slot_no)
     }
 
-    pub fn get_slot(&mut self, slot_no: SlotNo) -> Res<[u8; PAGE_RECORD_BYTE]> {
-        let num_slots = self.get_num_slots()?;
-        if slot_no.value >= num_slots {
-            return Err(Error::InvalidArg{ msg: format!("RecordPage::get_slot : slot_no must be less than {}", num_slots)});
+    // overwrite record of slot. returns false if the page has no space for it.
+    pub fn update_slot(&mut self, slot_no: SlotNo, data: &[u8]) -> Res<bool> {
+        let (offset, length, flags) = self.get_slot_entry(slot_no)?;
+        if offset == 0 || flags & SLOT_FORWARD != 0 {
+            return Err(Error::InvalidArg { 
+                msg: format!("RecordPage::update_slot: slot has no record. slot_no={}", slot_no.value) })
+        }
+        // overflow flag is set again by caller if needed
+        let flags = flags & SLOT_MOVED;
+        if tuple_space(data.len()) <= tuple_space(length) {
+            self.page.get_data_mut()[offset..offset+data.len()].copy_from_slice(data);
+            self.set_slot_entry(slot_no, offset, data.len(), flags)?;
+            return Ok(true);
+        }
+        if tuple_space(data.len()) > self.get_free_space()? + tuple_space(length) {
+            return Ok(false);
         }
+        self.set_slot_entry(slot_no, 0, 0, 0)?;
+        self.write_tuple(slot_no, data, flags)?;
+        Ok(true)
+    }
+
+    pub fn set_moved(&mut self, slot_no: SlotNo) -> Res<()> {
+        let (offset, length, flags) = self.get_slot_entry(slot_no)?;
+        self.set_slot_entry(slot_no, offset, length, flags | SLOT_MOVED)
+    }
 
-        if self.is_free_slot(slot_no)? {
+    // slot data must be (length, first_page_id) of overflow chain
+    pub fn set_overflow(&mut self, slot_no: SlotNo) -> Res<()> {
+        let (offset, length, flags) = self.get_slot_entry(slot_no)?;
+        if length != PAGE_FORWARD_BYTE {
             return Err(Error::InvalidArg { 
-                msg: format!(
-                    "RecordPage::get_slot: try to get free slot. slot_no={}",
-                    slot_no.value
-                ) })
+                msg: format!("RecordPage::set_overflow: invalid slot length {}. slot_no={}", length, slot_no.value) })
+        }
+        self.set_slot_entry(slot_no, offset, length, flags | SLOT_OVERFLOW)
+    }
+
+    // replace record by pointer to its new location. it always fits in place.
+    pub fn set_forward(&mut self, slot_no: SlotNo, rid: RecordId) -> Res<()> {
+        let (offset, _, _) = self.get_slot_entry(slot_no)?;
+        if offset == 0 {
+            return Err(Error::InvalidArg { 
+                msg: format!("RecordPage::set_forward: slot is free. slot_no={}", slot_no.value) })
+        }
+        self.page.set_int_value(offset, rid.page_id as i32)?;
+        self.page.set_int_value(offset + 4, rid.slot_no.value as i32)?;
+        self.set_slot_entry(slot_no, offset, PAGE_FORWARD_BYTE, SLOT_FORWARD)
+    }
+
+    // place data at the head of tuple area and point slot to it
+    fn write_tuple(&mut self, slot_no: SlotNo, data: &[u8], flags: usize) -> Res<()> {
+        let space = tuple_space(data.len());
+        let dir_end = self.pos_slot(SlotNo::new(self.get_num_slots()?));
+        if self.get_free_end()? < dir_end + space {
+            self.compact()?;
         }
+        let offset = self.get_free_end()? - space;
+        self.page.get_data_mut()[offset..offset+data.len()].copy_from_slice(data);
+        self.set_free_end(offset)?;
+        self.set_slot_entry(slot_no, offset, data.len(), flags)
+    }
 
-        let position = self.pos_slot(slot_no);
-        let mut data = [0; PAGE_RECORD_BYTE];
-        for i in 0..PAGE_RECORD_BYTE {
-            data[i] = self.page.get_byte_value(position + i)?;
+    // move tuple data to the tail so that free space becomes contiguous
+    fn compact(&mut self) -> Res<()> {
+        log::trace!("RecordPage::compact");
+        let num_slots = self.get_num_slots()?;
+        let mut tuples = Vec::new();
+        for i in 0..num_slots {
+            let slot_no = SlotNo::new(i);
+            let (offset, length, flags) = self.get_slot_entry(slot_no)?;
+            if offset > 0 {
+                let data = self.page.get_data()[offset..offset+tuple_space(length)].to_vec();
+                tuples.push((slot_no, length, flags, data));
+            }
         }
-        Ok(data)
+        let mut free_end = self.page.get_size();
+        for (slot_no, length, flags, data) in tuples {
+            free_end -= data.len();
+            self.page.get_data_mut()[free_end..free_end+data.len()].copy_from_slice(&data);
+            self.set_slot_entry(slot_no, free_end, length, flags)?;
+        }
+        self.set_free_end(free_end)
     }
 
     pub fn swap_slot(&mut self, i: SlotNo, j: SlotNo) -> Res<()> {
         log::trace!("RecordPage::swap_slot");
-        self.check_slot_no(i)?;
-        self.check_slot_no(j)?;
-
-        let tmp_bit = self.get_slot_bit(j)?;
-        let tmp_slot = self.get_slot(j)?;
-        let i_bit = self.get_slot_bit(i)?;
-        let i_slot = self.get_slot(i)?;
-        self.set_slot_bit(j, i_bit)?;
-        self.set_slot(j, i_slot)?;
-        self.set_slot_bit(i, tmp_bit)?;
-        self.set_slot(i, tmp_slot)?;
+        let (i_offset, i_length, i_flags) = self.get_slot_entry(i)?;
+        let (j_offset, j_length, j_flags) = self.get_slot_entry(j)?;
+        self.set_slot_entry(i, j_offset, j_length, j_flags)?;
+        self.set_slot_entry(j, i_offset, i_length, i_flags)?;
+        Ok(())
+    }
+
+    // drop free slots at the end of directory and make free space contiguous.
+    // slot numbers of live records don't change.
+    pub fn shrink(&mut self) -> Res<()> {
+        let mut num_slots = self.get_num_slots()?;
+        while num_slots > 0 && self.is_free_slot(SlotNo::new(num_slots - 1))? {
+            num_slots -= 1;
+        }
+        self.set_num_slots(num_slots)?;
+        self.compact()
+    }
 
+    pub fn free_all(&mut self) -> Res<()> {
+        self.set_num_slots(0)?;
+        self.set_free_end(self.page.get_size())?;
         Ok(())
     }
+}
 
-    fn check_slot_no(&mut self, slot_no: SlotNo) -> Res<()> {
-        let num_slots = self.get_num_slots()?;
-        if slot_no.value >= num_slots {
-            return Err(Error::InvalidArg{ msg: format!("RecordPage::set_slot : slot_no must be less than {}", num_slots)});
+// free-space map of heap file. each entry keeps approximate free bytes of record
+// page as a category, in units of page_size / FSM_NUM_CATEGORIES. entries are in
+// the order of the page chain, so the last entry is the tail page.
+// format
+// next_page_id: 4
+// num_entries: 4
+// max_category: 1   (largest category of entries, so that page is skipped by find)
+// entry_0: page_id 4, category 1
+// ...
+pub struct FreeSpaceMapPage<P> { page: P }
+
+impl<P: Deref<Target = Page>> FreeSpaceMapPage<P> {
+    pub fn new(page: P) -> Self {
+        Self { page }
+    }
+
+    // whole page fits in the largest category
+    fn unit(page_size: usize) -> usize {
+        page_size.div_ceil(FSM_NUM_CATEGORIES - 1)
+    }
+
+    // smallest category whose pages surely have `bytes` free
+    pub fn required_category(page_size: usize, bytes: usize) -> u8 {
+        bytes.div_ceil(Self::unit(page_size)).min(FSM_NUM_CATEGORIES - 1) as u8
+    }
+
+    pub fn category(page_size: usize, free_bytes: usize) -> u8 {
+        (free_bytes / Self::unit(page_size)).min(FSM_NUM_CATEGORIES - 1) as u8
+    }
+
+    fn capacity(&self) -> usize {
+        (self.page.get_size() - FSM_ENTRY_START) / FSM_ENTRY_BYTE
+    }
+
+    pub fn get_next_page_id(&self) -> Res<PageId> {
+        let page_id = self.page.get_int_value(FSM_NEXT_PAGE_ID)? as PageId;
+        Ok(page_id)
+    }
+
+    pub fn get_num_entries(&self) -> Res<usize> {
+        let num_entries = self.page.get_int_value(FSM_NUM_ENTRIES)? as usize;
+        Ok(num_entries)
+    }
+
+    fn pos_entry(&self, i: usize) -> usize {
+        FSM_ENTRY_START + FSM_ENTRY_BYTE * i
+    }
+
+    // (page_id, category)
+    pub fn get_entry(&self, i: usize) -> Res<(PageId, u8)> {
+        let position = self.pos_entry(i);
+        let page_id = self.page.get_int_value(position)? as PageId;
+        let category = self.page.get_byte_value(position + 4)?;
+        Ok((page_id, category))
+    }
+
+    pub fn get_max_category(&self) -> Res<u8> {
+        self.page.get_byte_value(FSM_MAX_CATEGORY)
+    }
+
+    pub fn find(&self, category: u8) -> Res<Option<PageId>> {
+        if self.get_max_category()? < category {
+            return Ok(None);
+        }
+        for i in 0..self.get_num_entries()? {
+            let (page_id, c) = self.get_entry(i)?;
+            if c >= category {
+                return Ok(Some(page_id));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn is_full(&self) -> Res<bool> {
+        Ok(self.get_num_entries()? >= self.capacity())
+    }
+}
+
+impl<P: DerefMut<Target = Page>> FreeSpaceMapPage<P> {
+    pub fn set_next_page_id(&mut self, page_id: PageId) -> Res<()> {
+        self.page.set_int_value(FSM_NEXT_PAGE_ID, page_id as i32)?;
+        Ok(())
+    }
+
+    pub fn add_entry(&mut self, page_id: PageId, category: u8) -> Res<()> {
+        let num_entries = self.get_num_entries()?;
+        if num_entries >= self.capacity() {
+            return Err(Error::InvalidArg { msg: "FreeSpaceMapPage::add_entry: page is full".to_string() });
+        }
+        let position = self.pos_entry(num_entries);
+        self.page.set_int_value(position, page_id as i32)?;
+        self.page.set_byte_value(position + 4, category)?;
+        self.page.set_int_value(FSM_NUM_ENTRIES, num_entries as i32 + 1)?;
+        if category > self.get_max_category()? {
+            self.page.set_byte_value(FSM_MAX_CATEGORY, category)?;
         }
         Ok(())
     }
 
-    pub fn free_all(&mut self) -> Res<()> {
-        for slot_no in 0..self.get_num_slots()? {
-            self.set_slot_bit(SlotNo::new(slot_no), 0)?
+    pub fn set_category(&mut self, i: usize, category: u8) -> Res<()> {
+        let (_, old) = self.get_entry(i)?;
+        let position = self.pos_entry(i);
+        self.page.set_byte_value(position + 4, category)?;
+        let max_category = self.get_max_category()?;
+        if category > max_category {
+            self.page.set_byte_value(FSM_MAX_CATEGORY, category)?;
+        } else if old == max_category && category < old {
+            // other entry may have the same category
+            let mut max_category = 0;
+            for i in 0..self.get_num_entries()? {
+                max_category = max_category.max(self.get_entry(i)?.1);
+            }
+            self.page.set_byte_value(FSM_MAX_CATEGORY, max_category)?;
         }
-        self.set_num_slots(0)?;
         Ok(())
     }
 }
 
+// page in chain holding part of record larger than a page
+// format
+// next_page_id: 4
+// length: 4
+// data
+pub struct OverflowPage<P> { page: P }
+
+impl<P: Deref<Target = Page>> OverflowPage<P> {
+    pub fn new(page: P) -> Self {
+        Self { page }
+    }
+
+    // bytes of record stored in one page of given size
+    pub fn capacity(page_size: usize) -> usize {
+        page_size - OVERFLOW_DATA_START
+    }
+
+    pub fn get_next_page_id(&self) -> Res<PageId> {
+        let page_id = self.page.get_int_value(OVERFLOW_NEXT_PAGE_ID)? as PageId;
+        Ok(page_id)
+    }
+
+    pub fn get_data(&self) -> Res<&[u8]> {
+        let length = self.page.get_int_value(OVERFLOW_LENGTH)? as usize;
+        Ok(&self.page.get_data()[OVERFLOW_DATA_START..OVERFLOW_DATA_START+length])
+    }
+}
+
+impl<P: DerefMut<Target = Page>> OverflowPage<P> {
+    pub fn set_next_page_id(&mut self, page_id: PageId) -> Res<()> {
+        self.page.set_int_value(OVERFLOW_NEXT_PAGE_ID, page_id as i32)?;
+        Ok(())
+    }
+
+    pub fn set_data(&mut self, data: &[u8]) -> Res<()> {
+        if data.len() > Self::capacity(self.page.get_size()) {
+            return Err(Error::InvalidArg { 
+                msg: format!("OverflowPage::set_data: data length ({}) exceeds page", data.len()) })
+        }
+        self.page.set_int_value(OVERFLOW_LENGTH, data.len() as i32)?;
+        self.page.get_data_mut()[OVERFLOW_DATA_START..OVERFLOW_DATA_START+data.len()].copy_from_slice(data);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::page::PAGE_BYTE;
+
+    use super::*;
+
+    #[test]
+    fn test_record_page() -> Res<()> {
+        let mut page = Page::new(PAGE_BYTE);
+        let mut record_page = RecordPage::new(&mut page);
+        let a = record_page.add_slot(&[1; 4])?;
+        let b = record_page.add_slot(&[2; 300])?;
+        let c = record_page.add_slot(&[3; 10])?;
+        assert_eq!(&[2; 300], record_page.get_slot(b)?);
+        assert_eq!(10, record_page.get_slot(c)?.len());
+
+        // freed slot and its space are reused
+        record_page.free_slot(b)?;
+        assert!(record_page.get_slot(b).is_err());
+        let max_len = record_page.get_free_space()?;
+        assert!(record_page.can_insert(max_len)?);
+        assert!(!record_page.can_insert(max_len + 1)?);
+        // compaction is required to fill fragmented space
+        let d = record_page.add_slot(&vec![4; max_len])?;
+        assert_eq!(b, d);
+        assert_eq!(0, record_page.get_free_space()?);
+        assert_eq!(&[1; 4], record_page.get_slot(a)?);
+        assert_eq!(&[3; 10], record_page.get_slot(c)?);
+        assert_eq!(max_len, record_page.get_slot(d)?.len());
+
+        record_page.swap_slot(a, c)?;
+        assert_eq!(&[1; 4], record_page.get_slot(c)?);
+
+        // short record keeps room for forwarding pointer
+        assert!(record_page.update_slot(c, &[6; 8])?);
+        assert_eq!(&[6; 8], record_page.get_slot(c)?);
+        assert!(!record_page.update_slot(c, &[6; 9])?);
+        let rid = RecordId::new(5, SlotNo::new(3));
+        record_page.set_forward(c, rid)?;
+        assert_eq!(Some(rid), record_page.get_forward(c)?);
+        assert!(record_page.get_slot(c).is_err());
+        assert_eq!(None, record_page.get_forward(a)?);
+
+        record_page.free_all()?;
+        assert_eq!(0, record_page.get_num_slots()?);
+        let max_len = RecordPage::<&Page>::max_record_len(page.get_size());
+        let mut record_page = RecordPage::new(&mut page);
+        record_page.add_slot(&vec![5; max_len])?;
+        assert!(!record_page.can_insert(0)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_page_directory_growth() -> Res<()> {
+        // leave 1..7 bytes between directory and tuple data, with fragmented space elsewhere
+        for gap in 1..PAGE_SLOT_BYTE {
+            let mut page = Page::new(PAGE_BYTE);
+            let mut record_page = RecordPage::new(&mut page);
+            let a = record_page.add_slot(&[1; 100])?;
+            let b = record_page.add_slot(&[2; 100])?;
+            let size = record_page.get_free_space()? - PAGE_SLOT_BYTE - gap;
+            let c = record_page.add_slot(&vec![3; size])?;
+            // shrinking in place leaves fragmented space but no free slot
+            assert!(record_page.update_slot(a, &[4; 8])?);
+            let d = record_page.add_slot(&[5; 8])?;
+            assert_eq!(&[4; 8], record_page.get_slot(a)?);
+            assert_eq!(&[2; 100], record_page.get_slot(b)?);
+            assert_eq!(&vec![3; size], record_page.get_slot(c)?);
+            assert_eq!(&[5; 8], record_page.get_slot(d)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_shrink_record_page() -> Res<()> {
+        let mut page = Page::new(PAGE_BYTE);
+        let mut record_page = RecordPage::new(&mut page);
+        let a = record_page.add_slot(&[1; 100])?;
+        let b = record_page.add_slot(&[2; 100])?;
+        let c = record_page.add_slot(&[3; 100])?;
+        let used = record_page.get_used_space()?;
+        record_page.free_slot(a)?;
+        record_page.free_slot(c)?;
+        assert_eq!(used, record_page.get_used_space()?);
+
+        // leading free slot is kept so that record id of b stays valid
+        record_page.shrink()?;
+        assert_eq!(2, record_page.get_num_slots()?);
+        assert_eq!(used - 200 - 8, record_page.get_used_space()?);
+        assert_eq!(&[2; 100], record_page.get_slot(b)?);
+
+        let (data, flags) = record_page.get_raw_slot(b)?;
+        record_page.free_slot(b)?;
+        record_page.shrink()?;
+        assert_eq!(0, record_page.get_num_slots()?);
+        let b = record_page.add_raw_slot(&data, flags)?;
+        assert_eq!(&[2; 100], record_page.get_slot(b)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_free_space_map_page() -> Res<()> {
+        let mut page = Page::new(PAGE_BYTE);
+        let mut fsm = FreeSpaceMapPage::new(&mut page);
+        fsm.add_entry(5, 10)?;
+        fsm.add_entry(6, 30)?;
+        fsm.add_entry(7, 30)?;
+        assert_eq!(30, fsm.get_max_category()?);
+        assert_eq!(Some(6), fsm.find(20)?);
+
+        // max category is kept while other entry has it
+        fsm.set_category(1, 0)?;
+        assert_eq!(30, fsm.get_max_category()?);
+        assert_eq!(Some(7), fsm.find(20)?);
+        fsm.set_category(2, 0)?;
+        assert_eq!(10, fsm.get_max_category()?);
+        assert_eq!(None, fsm.find(20)?);
+        fsm.set_category(0, 50)?;
+        assert_eq!(Some(5), fsm.find(20)?);
+        Ok(())
+    }
+}
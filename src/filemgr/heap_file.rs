@@ -1,36 +1,82 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 
 use crate::types::{Res, EntryNo, PageId, Error};
-use crate::bufmgr::BufMgr;
-use super::{PAGE_RECORD_BYTE, HeaderPage, RecordPage, with_record_page, with_header_page, SlotNo, create_page, RecordId, with_records_pages};
+use crate::bufmgr::{BufMgr, BufferRing};
+use crate::page::{Page, PAGE_HEADER_BYTE};
+use super::{HeaderPage, RecordPage, OverflowPage, FreeSpaceMapPage, with_record_page, with_record_page_read, with_header_page, with_header_page_read,
+    with_overflow_page, with_overflow_page_read, with_fsm_page, with_fsm_page_read, create_page, RecordId, SlotNo, with_records_pages};
 
 pub struct HeapFile {
     entry_no: EntryNo,
-    bufmgr: Arc<Mutex<BufMgr>>,
+    bufmgr: Arc<BufMgr>,
+    fsm_index: FsmIndex,
+}
+
+// what is known about free-space map of each file. it is shared by heap files
+// opened through the same HFileMgr.
+pub type FsmIndex = Arc<Mutex<HashMap<EntryNo, FsmEntries>>>;
+
+#[derive(Default)]
+pub struct FsmEntries {
+    // (map page, index) of the entry of each record page, so that the entry is
+    // updated without walking the map. built from the map on first update.
+    positions: Option<HashMap<PageId, (PageId, usize)>>,
+    // record pages found reachable from the head of the chain
+    linked: HashSet<PageId>,
+}
+
+// space given back by HeapFile::vacuum
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct VacuumStats {
+    // dead tuples, fragmentation and unused slots removed from record pages
+    pub bytes_reclaimed: usize,
+    // record pages unlinked from the chain and freed
+    pub pages_reclaimed: usize,
+    // (old, new) record id of each record moved to other page
+    pub moved: Vec<(RecordId, RecordId)>,
 }
 
 impl HeapFile {
-    pub fn new(entry_no: EntryNo, bufmgr: Arc<Mutex<BufMgr>>) -> HeapFile {
+    pub fn new(entry_no: EntryNo, bufmgr: Arc<BufMgr>, fsm_index: FsmIndex) -> HeapFile {
         HeapFile {
             entry_no,
             bufmgr,
+            fsm_index,
         }
     }
 
-    pub fn with_header_page<F, T>(&self, f: F) -> Res<T>
-    where F: FnOnce(&mut HeaderPage) -> Res<T> {
-        with_header_page(f, &self.bufmgr)
+    pub fn with_header_page_read<F, T>(&self, f: F) -> Res<T>
+    where F: FnOnce(&HeaderPage<&Page>) -> Res<T> {
+        with_header_page_read(f, self.entry_no.page_id, &self.bufmgr)
     }
 
     pub fn with_record_page<F, T>(&self, page_id: PageId, f: F) -> Res<T> 
-    where F: FnOnce(&mut RecordPage) -> Res<T> {
-        with_record_page(f, page_id, &self.bufmgr)
+    where F: FnOnce(&mut RecordPage<&mut Page>) -> Res<T> {
+        with_record_page(f, page_id, self.entry_no, &self.bufmgr)
+    }
+
+    pub fn with_record_page_read<F, T>(&self, page_id: PageId, f: F) -> Res<T> 
+    where F: FnOnce(&RecordPage<&Page>) -> Res<T> {
+        with_record_page_read(f, page_id, self.entry_no, &self.bufmgr)
+    }
+
+    // page is loaded into frames of the ring if it is not resident
+    pub fn with_record_page_read_in<F, T>(&self, page_id: PageId, ring: &BufferRing, f: F) -> Res<T> 
+    where F: FnOnce(&RecordPage<&Page>) -> Res<T> {
+        let page = self.bufmgr.fetch_page_read_in(page_id, Some(ring))?;
+        page.set_owner(self.entry_no);
+        f(&RecordPage::new(&page))
+    }
+
+    pub fn get_bufmgr(&self) -> Arc<BufMgr> {
+        self.bufmgr.clone()
     }
 
     pub fn with_record_pages<F>(&self, f: F) -> Res<()> 
-    where F: FnMut(PageId, &mut RecordPage) -> Res<()> {
+    where F: FnMut(PageId, &mut RecordPage<&mut Page>) -> Res<()> {
         let pid = self.get_header_free_page_id()?;
-        with_records_pages(f, pid, &self.bufmgr)
+        with_records_pages(f, pid, self.entry_no, &self.bufmgr)
         // while pid > 0 {
         //     let mut f = f;
         //     pid = self.with_record_page(pid, |p| {
@@ -47,86 +93,569 @@ impl HeapFile {
 
     pub fn get_entry_no(&self) -> EntryNo { self.entry_no }
 
-    pub fn insert_record(&mut self, data: [u8; PAGE_RECORD_BYTE]) -> Res<RecordId> {
-        let page_id = self.get_header_free_page_id()?;
-        self.insert_record_page(page_id, data)
+    // size of data area of page
+    fn get_page_size(&self) -> usize {
+        self.bufmgr.get_page_byte() - PAGE_HEADER_BYTE
+    }
+
+    // record larger than a page spills into overflow pages
+    pub fn insert_record(&mut self, data: &[u8]) -> Res<RecordId> {
+        let (slot_data, overflow) = self.prepare_slot_data(data)?;
+        let rid = self.insert_slot(&slot_data)?;
+        if overflow {
+            self.with_record_page(rid.page_id, |page| page.set_overflow(rid.slot_no))?;
+        }
+        Ok(rid)
+    }
+
+    // append records filling pages one after another from the tail of the chain.
+    // the page being filled stays pinned and free-space map is written once per page,
+    // so free space left in other pages isn't reused. returns number of records.
+    pub fn bulk_load<I, R>(&mut self, records: I) -> Res<usize>
+    where I: IntoIterator<Item = R>, R: AsRef<[u8]> {
+        let mut page_id = self.fsm_tail_page_id()?;
+        let mut guard = self.bufmgr.fetch_page_write(page_id)?;
+        guard.set_owner(self.entry_no);
+        let mut count = 0;
+        for record in records {
+            let (slot_data, overflow) = self.prepare_slot_data(record.as_ref())?;
+            let mut page = RecordPage::<&mut Page>::new(&mut guard);
+            if !page.can_insert(slot_data.len())? {
+                let free_bytes = page.get_free_space()?;
+                let new_page_id = create_page(&self.bufmgr)?;
+                page.set_next_page_id(new_page_id)?;
+                drop(guard);
+                self.fsm_update(page_id, free_bytes)?;
+                // entry of new tail is corrected when the page is finished
+                self.fsm_add(new_page_id, self.get_page_size())?;
+                guard = self.bufmgr.fetch_page_write(new_page_id)?;
+                guard.set_owner(self.entry_no);
+                RecordPage::<&mut Page>::new(&mut guard).set_prev_page_id(page_id)?;
+                page_id = new_page_id;
+            }
+            let mut page = RecordPage::<&mut Page>::new(&mut guard);
+            let slot_no = page.add_slot(&slot_data)?;
+            if overflow {
+                page.set_overflow(slot_no)?;
+            }
+            count += 1;
+        }
+        let free_bytes = RecordPage::<&Page>::new(&guard).get_free_space()?;
+        drop(guard);
+        self.fsm_update(page_id, free_bytes)?;
+        log::debug!("bulk load. entry_no={}, count={}", self.entry_no.value, count);
+        Ok(count)
+    }
+
+    // data stored in slot, and whether it points to overflow pages
+    fn prepare_slot_data(&self, data: &[u8]) -> Res<(Vec<u8>, bool)> {
+        if data.len() <= RecordPage::<&Page>::max_record_len(self.get_page_size()) {
+            return Ok((data.to_vec(), false));
+        }
+        let page_id = self.write_overflow(data)?;
+        let mut slot_data = Vec::new();
+        slot_data.extend_from_slice(&(data.len() as i32).to_le_bytes());
+        slot_data.extend_from_slice(&(page_id as i32).to_le_bytes());
+        Ok((slot_data, true))
+    }
+
+    // write data into new chain of overflow pages and return its first page
+    fn write_overflow(&self, data: &[u8]) -> Res<PageId> {
+        let capacity = OverflowPage::<&Page>::capacity(self.get_page_size());
+        let mut next_page_id = 0;
+        for chunk in data.chunks(capacity).rev() {
+            let page_id = create_page(&self.bufmgr)?;
+            with_overflow_page(|page| {
+                page.set_next_page_id(next_page_id)?;
+                page.set_data(chunk)
+            }, page_id, self.entry_no, &self.bufmgr)?;
+            next_page_id = page_id;
+        }
+        Ok(next_page_id)
+    }
+
+    fn read_overflow(&self, length: usize, first_page_id: PageId) -> Res<Vec<u8>> {
+        let mut data = Vec::with_capacity(length);
+        let mut page_id = first_page_id;
+        while page_id > 0 {
+            page_id = with_overflow_page_read(|page| {
+                data.extend_from_slice(page.get_data()?);
+                page.get_next_page_id()
+            }, page_id, self.entry_no, &self.bufmgr)?;
+        }
+        if data.len() != length {
+            return Err(Error::InvalidFormat {
+                msg: format!("HeapFile::read_overflow: expected {} bytes but found {}", length, data.len())
+            })
+        }
+        Ok(data)
+    }
+
+    fn free_overflow(&self, first_page_id: PageId) -> Res<()> {
+        let mut page_id = first_page_id;
+        while page_id > 0 {
+            let next_page_id = with_overflow_page_read(|page| page.get_next_page_id(),
+                page_id, self.entry_no, &self.bufmgr)?;
+            self.bufmgr.free_page(page_id)?;
+            page_id = next_page_id;
+        }
+        Ok(())
+    }
+
+    // put data into a page which has room for it according to free-space map,
+    // or into new page appended to the chain
+    fn insert_slot(&mut self, data: &[u8]) -> Res<RecordId> {
+        let required = RecordPage::<&Page>::required_space(data.len());
+        while let Some(page_id) = self.fsm_find(required)? {
+            let slot_no = self.modify_record_page(page_id, |page| {
+                if page.can_insert(data.len())? {
+                    Ok(Some(page.add_slot(data)?))
+                } else {
+                    Ok(None)
+                }
+            })?;
+            match slot_no {
+                Some(slot_no) => return Ok(RecordId::new(page_id, slot_no)),
+                // entry is corrected above, so the page isn't found again
+                None => log::trace!("free-space map is stale. pid={}", page_id),
+            }
+        }
+
+        log::trace!("no page has free space. create new one");
+        let tail_page_id = self.fsm_tail_page_id()?;
+        let new_page_id = create_page(&self.bufmgr)?;
+        self.with_record_page(tail_page_id, |page| {
+            page.set_next_page_id(new_page_id)
+        })?;
+        let (slot_no, free_bytes) = self.with_record_page(new_page_id, |new_page| {
+            new_page.set_prev_page_id(tail_page_id)?;
+            let slot_no = new_page.add_slot(data)?;
+            Ok((slot_no, new_page.get_free_space()?))
+        })?;
+        self.fsm_add(new_page_id, free_bytes)?;
+        log::trace!("new_page_id={}, slot_no={}", new_page_id, slot_no.value);
+        Ok(RecordId::new(new_page_id, slot_no))
+    }
+
+    // modify record page and record its free space in free-space map
+    fn modify_record_page<F, T>(&self, page_id: PageId, f: F) -> Res<T>
+    where F: FnOnce(&mut RecordPage<&mut Page>) -> Res<T> {
+        let (res, free_bytes) = self.with_record_page(page_id, |page| {
+            let res = f(page)?;
+            Ok((res, page.get_free_space()?))
+        })?;
+        self.fsm_update(page_id, free_bytes)?;
+        Ok(res)
+    }
+
+    // free-space map is built from the page chain on first use
+    fn get_fsm_page_id(&self) -> Res<PageId> {
+        let page_id = self.with_header_page_read(|h| h.get_fsm_page_id(self.entry_no))?;
+        if page_id > 0 {
+            return Ok(page_id);
+        }
+        log::debug!("build free-space map. entry_no={}", self.entry_no.value);
+        let fsm_page_id = create_page(&self.bufmgr)?;
+        with_header_page(|h| h.set_fsm_page_id(self.entry_no, fsm_page_id), self.entry_no.page_id, &self.bufmgr)?;
+        let mut page_id = self.get_header_free_page_id()?;
+        while page_id > 0 {
+            let (free_bytes, next_page_id) = self.with_record_page_read(page_id, |page| {
+                Ok((page.get_free_space()?, page.get_next_page_id()?))
+            })?;
+            self.fsm_add(page_id, free_bytes)?;
+            page_id = next_page_id;
+        }
+        Ok(fsm_page_id)
+    }
+
+    // page returned is in the chain. entry of page out of it is emptied, so that
+    // the page isn't found again.
+    fn fsm_find(&self, required: usize) -> Res<Option<PageId>> {
+        let category = FreeSpaceMapPage::<&Page>::required_category(self.get_page_size(), required);
+        let mut fsm_page_id = self.get_fsm_page_id()?;
+        while fsm_page_id > 0 {
+            let (page_id, next_page_id) = with_fsm_page_read(|fsm| {
+                Ok((fsm.find(category)?, fsm.get_next_page_id()?))
+            }, fsm_page_id, self.entry_no, &self.bufmgr)?;
+            match page_id {
+                Some(page_id) if self.is_linked(page_id)? => return Ok(Some(page_id)),
+                Some(page_id) => self.fsm_update(page_id, 0)?,
+                None => fsm_page_id = next_page_id,
+            }
+        }
+        Ok(None)
+    }
+
+    // whether page is reachable from the head of the chain. map is a hint since it
+    // isn't written together with the chain, and crash may leave entries of pages
+    // which didn't get linked or miss pages linked last.
+    fn is_linked(&self, page_id: PageId) -> Res<bool> {
+        let head_page_id = self.get_header_free_page_id()?;
+        let mut path = HashSet::new();
+        let mut page_id = page_id;
+        // walk back until the head or a page found linked before
+        let linked = loop {
+            if page_id == head_page_id || self.fsm_entries(|e| e.linked.contains(&page_id)) {
+                break true;
+            }
+            let prev_page_id = self.with_record_page_read(page_id, |page| page.get_prev_page_id())?;
+            if prev_page_id == 0 || path.contains(&prev_page_id) {
+                break false;
+            }
+            let next_page_id = self.with_record_page_read(prev_page_id, |page| page.get_next_page_id())?;
+            if next_page_id != page_id {
+                break false;
+            }
+            path.insert(page_id);
+            page_id = prev_page_id;
+        };
+        if linked {
+            self.fsm_entries(|e| e.linked.extend(path));
+        }
+        Ok(linked)
+    }
+
+    fn fsm_entries<F, T>(&self, f: F) -> T
+    where F: FnOnce(&mut FsmEntries) -> T {
+        f(self.fsm_index.lock().unwrap().entry(self.entry_no).or_default())
+    }
+
+    // last record page of the chain. pages are added to the map in the order of
+    // the chain, so it is the last entry unless crash left the map out of date.
+    fn fsm_tail_page_id(&self) -> Res<PageId> {
+        let mut page_ids = Vec::new();
+        let mut fsm_page_id = self.get_fsm_page_id()?;
+        while fsm_page_id > 0 {
+            fsm_page_id = with_fsm_page_read(|fsm| {
+                for i in 0..fsm.get_num_entries()? {
+                    page_ids.push(fsm.get_entry(i)?.0);
+                }
+                fsm.get_next_page_id()
+            }, fsm_page_id, self.entry_no, &self.bufmgr)?;
+        }
+        let mut tail_page_id = self.get_header_free_page_id()?;
+        // entries of pages out of the chain are at the end of the map
+        for page_id in page_ids.into_iter().rev() {
+            if self.is_linked(page_id)? {
+                tail_page_id = page_id;
+                break;
+            }
+            self.fsm_update(page_id, 0)?;
+        }
+        // pages linked after the map was written are added to it
+        loop {
+            let (next_page_id, free_bytes) = self.with_record_page_read(tail_page_id, |page| {
+                Ok((page.get_next_page_id()?, page.get_free_space()?))
+            })?;
+            if self.fsm_position(tail_page_id)?.is_none() {
+                self.fsm_add(tail_page_id, free_bytes)?;
+            }
+            if next_page_id == 0 {
+                return Ok(tail_page_id);
+            }
+            self.fsm_entries(|e| e.linked.insert(next_page_id));
+            tail_page_id = next_page_id;
+        }
+    }
+
+    fn fsm_add(&self, page_id: PageId, free_bytes: usize) -> Res<()> {
+        let category = FreeSpaceMapPage::<&Page>::category(self.get_page_size(), free_bytes);
+        let mut fsm_page_id = self.get_fsm_page_id()?;
+        loop {
+            let (next_page_id, is_full) = with_fsm_page_read(|fsm| {
+                Ok((fsm.get_next_page_id()?, fsm.is_full()?))
+            }, fsm_page_id, self.entry_no, &self.bufmgr)?;
+            if next_page_id > 0 {
+                fsm_page_id = next_page_id;
+            } else if !is_full {
+                let i = with_fsm_page(|fsm| {
+                    fsm.add_entry(page_id, category)?;
+                    Ok(fsm.get_num_entries()? - 1)
+                }, fsm_page_id, self.entry_no, &self.bufmgr)?;
+                self.fsm_entries(|e| {
+                    if let Some(positions) = e.positions.as_mut() {
+                        positions.insert(page_id, (fsm_page_id, i));
+                    }
+                });
+                return Ok(());
+            } else {
+                let new_page_id = create_page(&self.bufmgr)?;
+                with_fsm_page(|fsm| fsm.set_next_page_id(new_page_id),
+                    fsm_page_id, self.entry_no, &self.bufmgr)?;
+                fsm_page_id = new_page_id;
+            }
+        }
     }
 
-    fn insert_record_page(&mut self, page_id: PageId, data: [u8; PAGE_RECORD_BYTE]) -> Res<RecordId> {
-        if page_id == 0 {
-            return Err(Error::NoFreePage);
+    fn fsm_update(&self, page_id: PageId, free_bytes: usize) -> Res<()> {
+        let category = FreeSpaceMapPage::<&Page>::category(self.get_page_size(), free_bytes);
+        let Some((fsm_page_id, i)) = self.fsm_position(page_id)? else {
+            return Ok(());
+        };
+        let c = with_fsm_page_read(|fsm| Ok(fsm.get_entry(i)?.1), fsm_page_id, self.entry_no, &self.bufmgr)?;
+        // map page is written only if the category changes
+        if c != category {
+            with_fsm_page(|fsm| fsm.set_category(i, category), fsm_page_id, self.entry_no, &self.bufmgr)?;
         }
+        Ok(())
+    }
+
+    // (map page, index) of entry of record page
+    fn fsm_position(&self, page_id: PageId) -> Res<Option<(PageId, usize)>> {
+        // map is built before index is locked, since building adds entries
+        let mut fsm_page_id = self.get_fsm_page_id()?;
+        let mut index = self.fsm_index.lock().unwrap();
+        let entries = index.entry(self.entry_no).or_default();
+        let positions = match &mut entries.positions {
+            Some(positions) => positions,
+            None => {
+                let mut positions = HashMap::new();
+                while fsm_page_id > 0 {
+                    let map_page_id = fsm_page_id;
+                    fsm_page_id = with_fsm_page_read(|fsm| {
+                        for i in 0..fsm.get_num_entries()? {
+                            positions.insert(fsm.get_entry(i)?.0, (map_page_id, i));
+                        }
+                        fsm.get_next_page_id()
+                    }, map_page_id, self.entry_no, &self.bufmgr)?;
+                }
+                entries.positions.insert(positions)
+            }
+        };
+        Ok(positions.get(&page_id).copied())
+    }
 
-        let success = self.with_record_page(page_id,
-             |page| {
-            let num_slots = page.get_num_slots()?;
-            if page.capasity() == num_slots {
-                log::trace!("this page is full. serch free slot. pid={}", page_id);
-                // search free page
-                for i in 0..num_slots {
+    // map is rebuilt on next use
+    fn free_fsm(&self) -> Res<()> {
+        self.fsm_index.lock().unwrap().remove(&self.entry_no);
+        let mut fsm_page_id = self.with_header_page_read(|h| h.get_fsm_page_id(self.entry_no))?;
+        with_header_page(|h| h.set_fsm_page_id(self.entry_no, 0), self.entry_no.page_id, &self.bufmgr)?;
+        while fsm_page_id > 0 {
+            let next_page_id = with_fsm_page_read(|fsm| fsm.get_next_page_id(),
+                fsm_page_id, self.entry_no, &self.bufmgr)?;
+            self.bufmgr.free_page(fsm_page_id)?;
+            fsm_page_id = next_page_id;
+        }
+        Ok(())
+    }
+
+    // remove all records. pages are kept for later inserts.
+    pub fn truncate(&self) -> Res<()> {
+        let mut overflow_page_ids = Vec::new();
+        self.with_record_pages(|_pid, page| {
+            overflow_page_ids.extend(page.get_overflow_page_ids()?);
+            page.free_all()
+        })?;
+        for page_id in overflow_page_ids {
+            self.free_overflow(page_id)?;
+        }
+        self.free_fsm()
+    }
+
+    // release every page of the file to the disk. the file must not be used afterwards.
+    pub fn free_pages(&self) -> Res<()> {
+        let mut page_id = self.get_header_free_page_id()?;
+        while page_id > 0 {
+            let (overflow_page_ids, next_page_id) = self.with_record_page_read(page_id, |page| {
+                Ok((page.get_overflow_page_ids()?, page.get_next_page_id()?))
+            })?;
+            for overflow_page_id in overflow_page_ids {
+                self.free_overflow(overflow_page_id)?;
+            }
+            self.bufmgr.free_page(page_id)?;
+            page_id = next_page_id;
+        }
+        self.free_fsm()
+    }
+
+    // move live records toward the head of the page chain and free pages emptied by it.
+    // record moved to other page gets new record id, which is returned in `moved`.
+    // relocated record keeps the id of its forwarding slot unless the slot is moved.
+    pub fn vacuum(&mut self) -> Res<VacuumStats> {
+        let mut page_ids = Vec::new();
+        let mut used_before = 0;
+        // original slot of each relocated record
+        let mut origins = HashMap::new();
+        self.with_record_pages(|page_id, page| {
+            page_ids.push(page_id);
+            used_before += page.get_used_space()?;
+            for i in 0..page.get_num_slots()? {
+                let slot_no = SlotNo::new(i);
+                if let Some(target) = page.get_forward(slot_no)? {
+                    origins.insert(target, RecordId::new(page_id, slot_no));
+                }
+            }
+            Ok(())
+        })?;
+
+        // records of the tail page are moved into pages from the head until it becomes empty
+        let mut pages_reclaimed = 0;
+        let mut moved_rids = Vec::new();
+        let mut front = 0;
+        let mut back = page_ids.len().saturating_sub(1);
+        'merge: while front < back {
+            let back_page_id = page_ids[back];
+            let slots = self.with_record_page_read(back_page_id, |page| {
+                let mut slots = Vec::new();
+                for i in 0..page.get_num_slots()? {
                     let slot_no = SlotNo::new(i);
-                    if page.is_free_slot(slot_no)? {
-                        // found free slot
-                        log::trace!("found free slot. pid={}, sno={}", page_id, slot_no.value);
-                        page.set_slot(slot_no, data)?;
-                        return Ok(Some(RecordId::new(page_id, slot_no)))
+                    if !page.is_free_slot(slot_no)? {
+                        slots.push((slot_no, page.get_forward(slot_no)?, page.is_moved_slot(slot_no)?));
                     }
                 }
-                log::trace!("free slot not found. pid={}", page_id);
-                return Ok(None)
+                Ok(slots)
+            })?;
+            for (slot_no, forward, moved) in slots {
+                let rid = RecordId::new(back_page_id, slot_no);
+                let new_rid = loop {
+                    if front == back {
+                        break 'merge;
+                    }
+                    match self.move_slot(rid, page_ids[front])? {
+                        Some(new_rid) => break new_rid,
+                        None => front += 1,
+                    }
+                };
+                if let Some(target) = forward {
+                    origins.insert(target, new_rid);
+                }
+                if moved {
+                    let origin = origins.remove(&rid).ok_or(Error::Corruption { page_id: back_page_id })?;
+                    self.with_record_page(origin.page_id, |page| page.set_forward(origin.slot_no, new_rid))?;
+                    origins.insert(new_rid, origin);
+                } else {
+                    moved_rids.push((rid, new_rid));
+                }
+            }
+            self.unlink_page(back_page_id, page_ids[back - 1])?;
+            self.bufmgr.free_page(back_page_id)?;
+            pages_reclaimed += 1;
+            back -= 1;
+        }
+
+        page_ids.truncate(back + 1);
+        let mut used_after = 0;
+        for page_id in page_ids {
+            used_after += self.with_record_page(page_id, |page| {
+                page.shrink()?;
+                page.get_used_space()
+            })?;
+        }
+        self.free_fsm()?;
+        let stats = VacuumStats {
+            bytes_reclaimed: used_before.saturating_sub(used_after),
+            pages_reclaimed,
+            moved: moved_rids,
+        };
+        log::debug!("vacuum. entry_no={}, bytes_reclaimed={}, pages_reclaimed={}, moved={}",
+            self.entry_no.value, stats.bytes_reclaimed, stats.pages_reclaimed, stats.moved.len());
+        Ok(stats)
+    }
+
+    // move tuple into the page keeping its flags. returns None if it doesn't fit.
+    fn move_slot(&self, rid: RecordId, page_id: PageId) -> Res<Option<RecordId>> {
+        let (data, flags) = self.with_record_page_read(rid.page_id, |page| page.get_raw_slot(rid.slot_no))?;
+        let slot_no = self.with_record_page(page_id, |page| {
+            if page.can_insert(data.len())? {
+                Ok(Some(page.add_raw_slot(&data, flags)?))
             } else {
-                // add new slot
-                let slot_no = page.add_slot(data)?;
-                log::trace!("this page has free space. add new slot. pid={}, sno={}", page_id, slot_no.value);
-                return Ok(Some(RecordId::new(page_id, slot_no)))
+                Ok(None)
             }
         })?;
+        match slot_no {
+            Some(slot_no) => {
+                self.with_record_page(rid.page_id, |page| page.free_slot(rid.slot_no))?;
+                Ok(Some(RecordId::new(page_id, slot_no)))
+            }
+            None => Ok(None),
+        }
+    }
 
-        match success {
-            Some(rid) => Ok(rid),
-            None => {
-                // explore next page
-                let next_page_id = self.with_record_page(page_id,
-                    |page| 
-                    page.get_next_page_id())?;
-                if next_page_id > 1 {
-                    // next page found
-                    log::trace!("next page found. page_id={}", next_page_id);
-                    self.insert_record_page(next_page_id, data)
-                } else {
-                    // next page isn't exists. create new one
-                    log::trace!("next page doesn't found. create new one");
-                    let new_page_id = create_page(&self.bufmgr)?;
-                    self.with_record_page(page_id, |page| {
-                        page.set_next_page_id(new_page_id)
-                    })?;
-                    let slot_no = self.with_record_page(new_page_id, |new_page| {
-                        new_page.set_prev_page_id(page_id)?;
-                        new_page.add_slot(data)
-                    })?;
-                    log::trace!("new_page_id={}, slot_no={}", new_page_id, slot_no.value);
-                    Ok(RecordId::new(new_page_id, slot_no))
+    fn unlink_page(&self, page_id: PageId, prev_page_id: PageId) -> Res<()> {
+        let next_page_id = self.with_record_page_read(page_id, |page| page.get_next_page_id())?;
+        self.with_record_page(prev_page_id, |page| page.set_next_page_id(next_page_id))?;
+        if next_page_id > 0 {
+            self.with_record_page(next_page_id, |page| page.set_prev_page_id(prev_page_id))?;
+        }
+        Ok(())
+    }
+
+    // record id stays valid after update. record which doesn't fit in its page is
+    // relocated and original slot keeps forwarding pointer to it.
+    pub fn update_record(&mut self, rid: RecordId, data: &[u8]) -> Res<()> {
+        let forward = self.with_record_page_read(rid.page_id, |page| page.get_forward(rid.slot_no))?;
+        let target = forward.unwrap_or(rid);
+        let old_overflow = self.with_record_page_read(target.page_id, |page| page.get_overflow(target.slot_no))?;
+        let (slot_data, overflow) = self.prepare_slot_data(data)?;
+        let updated = self.modify_record_page(target.page_id, |page| {
+            if !page.update_slot(target.slot_no, &slot_data)? {
+                return Ok(false);
+            }
+            if overflow {
+                page.set_overflow(target.slot_no)?;
+            }
+            Ok(true)
+        })?;
+
+        if !updated {
+            log::trace!("relocate record. rid={}", rid);
+            let new_rid = self.insert_slot(&slot_data)?;
+            self.with_record_page(new_rid.page_id, |page| {
+                page.set_moved(new_rid.slot_no)?;
+                if overflow {
+                    page.set_overflow(new_rid.slot_no)?;
                 }
+                Ok(())
+            })?;
+            self.modify_record_page(rid.page_id, |page| page.set_forward(rid.slot_no, new_rid))?;
+            if forward.is_some() {
+                self.modify_record_page(target.page_id, |page| page.free_slot(target.slot_no))?;
             }
         }
+        if let Some((_, page_id)) = old_overflow {
+            self.free_overflow(page_id)?;
+        }
+        Ok(())
     }
 
-    pub fn get_record(&self, rid: RecordId) -> Res<[u8; PAGE_RECORD_BYTE]> {
-        self.with_record_page(rid.page_id, |page| {
-            let data = page.get_slot(rid.slot_no)?;
-            Ok(data)
-        })
+    pub fn get_record(&self, rid: RecordId) -> Res<Vec<u8>> {
+        let forward = self.with_record_page_read(rid.page_id, |page| page.get_forward(rid.slot_no))?;
+        let rid = forward.unwrap_or(rid);
+        let (overflow, data) = self.with_record_page_read(rid.page_id, |page| {
+            match page.get_overflow(rid.slot_no)? {
+                Some(overflow) => Ok((Some(overflow), Vec::new())),
+                None => Ok((None, page.get_slot(rid.slot_no)?.to_vec())),
+            }
+        })?;
+        match overflow {
+            Some((length, page_id)) => self.read_overflow(length, page_id),
+            None => Ok(data),
+        }
     }
 
     pub fn delete_record(&mut self, rid: RecordId) -> Res<()> {
-        self.with_record_page(rid.page_id, |page| {
-            page.set_slot_bit(rid.slot_no, 0)?;
-            Ok(())
-        })
+        if let Some(target) = self.free_record_slot(rid)? {
+            self.free_record_slot(target)?;
+        }
+        Ok(())
+    }
+
+    // free slot and its overflow pages. returns location of relocated record if slot forwards to it.
+    fn free_record_slot(&self, rid: RecordId) -> Res<Option<RecordId>> {
+        let (forward, overflow) = self.modify_record_page(rid.page_id, |page| {
+            let forward = page.get_forward(rid.slot_no)?;
+            let overflow = page.get_overflow(rid.slot_no)?;
+            page.free_slot(rid.slot_no)?;
+            Ok((forward, overflow))
+        })?;
+        if let Some((_, page_id)) = overflow {
+            self.free_overflow(page_id)?;
+        }
+        Ok(forward)
     }
 
     pub fn get_header_free_page_id(&self) -> Res<PageId> {
-        let page_id = self.with_header_page(|h| {
+        let page_id = self.with_header_page_read(|h| {
             h.get_head_free_page_id(self.entry_no)
         })?;
         Ok(page_id)
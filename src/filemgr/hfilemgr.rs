@@ -1,53 +1,105 @@
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
 use crate::types::*;
-use crate::bufmgr::BufMgr;
+use crate::bufmgr::{BufMgr, ReplacerKind, BackgroundWriter, BackgroundWriterConfig, BufMgrStats, FrameInfo};
+use crate::diskmgr::PageStore;
 use super::heap_file::*;
 use super::*;
 
 pub struct HFileMgr {
-    bufmgr: Arc<Mutex<BufMgr>>,
+    // stopped before buffer pool is dropped
+    background_writer: Option<BackgroundWriter>,
+    bufmgr: Arc<BufMgr>,
     header_page_id: PageId,
+    fsm_index: FsmIndex,
 }
 
+// number of frames of buffer pool unless it is given
+pub const DEFAULT_MAX_BUFSIZE: usize = 10;
+
 impl HFileMgr {
-    pub fn build(bufmgr: Arc<Mutex<BufMgr>>) -> Res<Self> {
-        let cloned = bufmgr.clone();
-        let mut mgr = bufmgr.lock().unwrap();
-        let (header_page_id, _) =mgr.create_page()?;
+    pub fn build(bufmgr: Arc<BufMgr>) -> Res<Self> {
+        let header_page_id = match bufmgr.get_catalog_root() {
+            0 => {
+                // new database
+                let page_id = bufmgr.new_page()?.get_page_id();
+                bufmgr.set_catalog_root(page_id)?;
+                page_id
+            }
+            page_id => page_id,
+        };
         let mgr = Self {
-            bufmgr: cloned,
+            background_writer: None,
+            bufmgr,
             header_page_id,
+            fsm_index: Arc::new(Mutex::new(HashMap::new())),
         };
         Ok(mgr)
     }
 
     pub fn build_default(db_name: &str) -> Res<Self> {
         let diskmgr = DiskMgr::open_db(db_name)?;
-        let bufmgr = BufMgr::new(10, diskmgr);
-        let bufmgr = Arc::new(Mutex::new(bufmgr));
-        let filemgr = HFileMgr::build(bufmgr)?;
-        Ok(filemgr)
+        Self::build_on(diskmgr)
+    }
+
+    pub fn build_on(store: impl PageStore + 'static) -> Res<Self> {
+        Self::build_on_with_replacer(store, ReplacerKind::Lru, DEFAULT_MAX_BUFSIZE)
+    }
+
+    pub fn build_on_with_replacer(store: impl PageStore + 'static, kind: ReplacerKind, max_bufsize: usize) -> Res<Self> {
+        let bufmgr = BufMgr::with_replacer(max_bufsize, store, kind);
+        HFileMgr::build(Arc::new(bufmgr))
+    }
+
+    pub fn get_page_byte(&self) -> usize {
+        self.bufmgr.get_page_byte()
+    }
+
+    pub fn start_background_writer(&mut self, config: BackgroundWriterConfig) {
+        self.background_writer = Some(BackgroundWriter::start(&self.bufmgr, config));
+    }
+
+    pub fn get_bufmgr_stats(&self) -> BufMgrStats {
+        self.bufmgr.get_stats()
+    }
+
+    pub fn get_frames(&self) -> Vec<FrameInfo> {
+        self.bufmgr.get_frames()
     }
 
-    fn with_header_page<F, T>(&self, f: F) -> Res<T>
-    where F: FnOnce(&mut HeaderPage) -> Res<T> {
-        with_header_page(f, &self.bufmgr)
+    pub fn get_file_name(&self, entry_no: EntryNo) -> Res<String> {
+        self.with_header_page_read(entry_no.page_id, |header_page| {
+            header_page.get_name(entry_no)
+        })
     }
 
-    fn with_record_page<F, T>(&self, page_id: PageId, f: F) -> Res<T> 
-    where F: FnOnce(&mut RecordPage) -> Res<T> {
-        with_record_page(f, page_id, &self.bufmgr)
+    pub fn close(&mut self) -> Res<()> {
+        self.bufmgr.flush_all()
+    }
+
+    fn with_header_page<F, T>(&self, page_id: PageId, f: F) -> Res<T>
+    where F: FnOnce(&mut HeaderPage<&mut Page>) -> Res<T> {
+        with_header_page(f, page_id, &self.bufmgr)
+    }
+
+    fn with_header_page_read<F, T>(&self, page_id: PageId, f: F) -> Res<T>
+    where F: FnOnce(&HeaderPage<&Page>) -> Res<T> {
+        with_header_page_read(f, page_id, &self.bufmgr)
+    }
+
+    fn with_record_page<F, T>(&self, page_id: PageId, owner: EntryNo, f: F) -> Res<T> 
+    where F: FnOnce(&mut RecordPage<&mut Page>) -> Res<T> {
+        with_record_page(f, page_id, owner, &self.bufmgr)
     }
 
     fn create_page(&mut self) -> Res<PageId> {
-        let mut bufmgr = self.bufmgr.lock().unwrap();
-        let (page_id, _) = bufmgr.create_page()?;
-        Ok(page_id)
+        create_page(&self.bufmgr)
     }
 
     pub fn create_file(&mut self, name: &str) -> Res<HeapFile> {
-        if let Some(_) = self.find_file(name)? {
+        check_name(name, "create_file")?;
+        if self.find_file(name)?.is_some() {
             return Err(Error::InvalidArg { 
                 msg: format!(
                     "HFileMgr::create_file: file already exists. name={}",
@@ -56,24 +108,27 @@ impl HFileMgr {
         }
         let page_id = self.create_page()?;
 
-        let entry_no = self.with_header_page(|header_page| {
-            let entry_no = header_page.new_entry()?;
+        let entry_no = self.new_entry()?;
+        self.with_header_page(entry_no.page_id, |header_page| {
             header_page.set_head_free_page_id(entry_no, page_id)?;
-            header_page.set_head_full_page_id(entry_no, 0)?;
-            header_page.set_name(entry_no, name)?;
-            Ok(entry_no)
+            header_page.set_fsm_page_id(entry_no, 0)?;
+            header_page.set_name(entry_no, name)
         })?;
 
         // FIXME: summarize as new page procedure
         let hpid = self.header_page_id;
-        self.with_record_page(page_id, |page| {
+        self.with_record_page(page_id, entry_no, |page| {
             page.set_next_page_id(0)?; // set invalid page id
             page.set_prev_page_id(hpid)?;
             Ok(())
         })?;
 
         let bufmgr = self.bufmgr.clone();
-        Ok(HeapFile::new(entry_no, bufmgr))
+        Ok(HeapFile::new(entry_no, bufmgr, self.fsm_index.clone()))
+    }
+
+    pub fn contains_file(&mut self, name: &str) -> Res<bool> {
+        Ok(self.find_file(name)?.is_some())
     }
 
     pub fn open(&mut self, name: &str) -> Res<HeapFile> {
@@ -81,53 +136,159 @@ impl HFileMgr {
             None => self.create_file(name),
             Some(entry_no) => {
                 let bufmgr = self.bufmgr.clone();
-                Ok(HeapFile::new(entry_no, bufmgr))
+                Ok(HeapFile::new(entry_no, bufmgr, self.fsm_index.clone()))
             }
         }
     }
 
-    fn find_file(&mut self, name: &str) -> Res<Option<EntryNo>> {
-        self.with_header_page(|header_page| {
-            header_page.find(name)
+    // pages of the file are released and its entry can be used by new file.
+    // HeapFile opened before must not be used afterwards.
+    pub fn drop_file(&mut self, name: &str) -> Res<()> {
+        let entry_no = self.get_entry_no(name, "drop_file")?;
+        HeapFile::new(entry_no, self.bufmgr.clone(), self.fsm_index.clone()).free_pages()?;
+        self.with_header_page(entry_no.page_id, |header_page| {
+            header_page.set_head_free_page_id(entry_no, 0)?;
+            header_page.set_fsm_page_id(entry_no, 0)?;
+            header_page.set_name(entry_no, "")
+        })
+    }
+
+    pub fn rename_file(&mut self, name: &str, new_name: &str) -> Res<()> {
+        check_name(new_name, "rename_file")?;
+        if self.find_file(new_name)?.is_some() {
+            return Err(Error::InvalidArg { 
+                msg: format!(
+                    "HFileMgr::rename_file: file already exists. name={}",
+                    new_name)
+            })
+        }
+        let entry_no = self.get_entry_no(name, "rename_file")?;
+        self.with_header_page(entry_no.page_id, |header_page| {
+            header_page.set_name(entry_no, new_name)
+        })
+    }
+
+    fn get_entry_no(&mut self, name: &str, caller: &str) -> Res<EntryNo> {
+        self.find_file(name)?.ok_or_else(|| Error::InvalidArg {
+            msg: format!("HFileMgr::{}: file not found. name={}", caller, name)
         })
     }
+
+    // unused entry in directory. new directory page is appended when all pages are full.
+    fn new_entry(&mut self) -> Res<EntryNo> {
+        let mut page_id = self.header_page_id;
+        loop {
+            let (i, next_page_id) = self.with_header_page_read(page_id, |header_page| {
+                Ok((header_page.new_entry()?, header_page.get_next_page_id()?))
+            })?;
+            if let Some(i) = i {
+                return Ok(EntryNo::new(page_id, i));
+            }
+            if next_page_id == 0 {
+                let new_page_id = self.create_page()?;
+                log::debug!("add directory page. page_id={}", new_page_id);
+                self.with_header_page(page_id, |header_page| {
+                    header_page.set_next_page_id(new_page_id)
+                })?;
+                return Ok(EntryNo::new(new_page_id, 0));
+            }
+            page_id = next_page_id;
+        }
+    }
+
+    fn find_file(&mut self, name: &str) -> Res<Option<EntryNo>> {
+        let mut page_id = self.header_page_id;
+        while page_id > 0 {
+            let (i, next_page_id) = self.with_header_page_read(page_id, |header_page| {
+                Ok((header_page.find(name)?, header_page.get_next_page_id()?))
+            })?;
+            if let Some(i) = i {
+                return Ok(Some(EntryNo::new(page_id, i)));
+            }
+            page_id = next_page_id;
+        }
+        Ok(None)
+    }
 }
 
-pub fn with_header_page<F, T>(f: F, mutex: &Arc<Mutex<BufMgr>>) -> Res<T>
-where F: FnOnce(&mut HeaderPage) -> Res<T> {
-    let header_page_id = 1;
-    let mut bufmgr = mutex.lock().unwrap();
-    let header_page = bufmgr.pin_page(header_page_id)?;
-    let mut header_page = HeaderPage::new(header_page);
-    let res = f(&mut header_page);
-    bufmgr.unpin_page(header_page_id)?;
-    res
+// name has to fit in directory entry
+fn check_name(name: &str, caller: &str) -> Res<()> {
+    if name.len() > HEADER_NAME_BYTE {
+        return Err(Error::InvalidArg {
+            msg: format!("HFileMgr::{}: name must be at most {} bytes. name={}", caller, HEADER_NAME_BYTE, name)
+        });
+    }
+    Ok(())
 }
 
-pub fn with_record_page<F, T>(f: F, page_id: PageId, mutex: &Arc<Mutex<BufMgr>>) -> Res<T>
-where F: FnOnce(&mut RecordPage) -> Res<T> {
-    let mut bufmgr = mutex.lock().unwrap();
-    let page = bufmgr.pin_page(page_id)?;
-    let mut page = RecordPage::new(page);
-    let res = f(&mut page);
-    bufmgr.unpin_page(page_id)?;
-    res
+pub fn with_header_page<F, T>(f: F, page_id: PageId, bufmgr: &BufMgr) -> Res<T>
+where F: FnOnce(&mut HeaderPage<&mut Page>) -> Res<T> {
+    let mut page = bufmgr.fetch_page_write(page_id)?;
+    f(&mut HeaderPage::new(&mut page))
 }
 
-pub fn with_records_pages<F>(f: F, first_page_id: PageId, mutex: &Arc<Mutex<BufMgr>>) -> Res<()> 
-where F: FnMut(PageId, &mut RecordPage) -> Res<()> {
+pub fn with_header_page_read<F, T>(f: F, page_id: PageId, bufmgr: &BufMgr) -> Res<T>
+where F: FnOnce(&HeaderPage<&Page>) -> Res<T> {
+    let page = bufmgr.fetch_page_read(page_id)?;
+    f(&HeaderPage::new(&page))
+}
+
+// record pages are tagged with heap file owning them
+pub fn with_record_page<F, T>(f: F, page_id: PageId, owner: EntryNo, bufmgr: &BufMgr) -> Res<T>
+where F: FnOnce(&mut RecordPage<&mut Page>) -> Res<T> {
+    let mut page = bufmgr.fetch_page_write(page_id)?;
+    page.set_owner(owner);
+    f(&mut RecordPage::new(&mut page))
+}
+
+pub fn with_record_page_read<F, T>(f: F, page_id: PageId, owner: EntryNo, bufmgr: &BufMgr) -> Res<T>
+where F: FnOnce(&RecordPage<&Page>) -> Res<T> {
+    let page = bufmgr.fetch_page_read(page_id)?;
+    page.set_owner(owner);
+    f(&RecordPage::new(&page))
+}
+
+pub fn with_fsm_page<F, T>(f: F, page_id: PageId, owner: EntryNo, bufmgr: &BufMgr) -> Res<T>
+where F: FnOnce(&mut FreeSpaceMapPage<&mut Page>) -> Res<T> {
+    let mut page = bufmgr.fetch_page_write(page_id)?;
+    page.set_owner(owner);
+    f(&mut FreeSpaceMapPage::new(&mut page))
+}
+
+pub fn with_fsm_page_read<F, T>(f: F, page_id: PageId, owner: EntryNo, bufmgr: &BufMgr) -> Res<T>
+where F: FnOnce(&FreeSpaceMapPage<&Page>) -> Res<T> {
+    let page = bufmgr.fetch_page_read(page_id)?;
+    page.set_owner(owner);
+    f(&FreeSpaceMapPage::new(&page))
+}
+
+pub fn with_overflow_page<F, T>(f: F, page_id: PageId, owner: EntryNo, bufmgr: &BufMgr) -> Res<T>
+where F: FnOnce(&mut OverflowPage<&mut Page>) -> Res<T> {
+    let mut page = bufmgr.fetch_page_write(page_id)?;
+    page.set_owner(owner);
+    f(&mut OverflowPage::new(&mut page))
+}
+
+pub fn with_overflow_page_read<F, T>(f: F, page_id: PageId, owner: EntryNo, bufmgr: &BufMgr) -> Res<T>
+where F: FnOnce(&OverflowPage<&Page>) -> Res<T> {
+    let page = bufmgr.fetch_page_read(page_id)?;
+    page.set_owner(owner);
+    f(&OverflowPage::new(&page))
+}
+
+pub fn with_records_pages<F>(f: F, first_page_id: PageId, owner: EntryNo, bufmgr: &BufMgr) -> Res<()> 
+where F: FnMut(PageId, &mut RecordPage<&mut Page>) -> Res<()> {
     let mut pid = first_page_id;
     let mut f = f;
     while pid > 0 {
-        let mut bufmgr = mutex.lock().unwrap();
-        let page = bufmgr.pin_page(pid)?;
-        let mut page = RecordPage::new(page);
+        let mut guard = bufmgr.fetch_page_write(pid)?;
+        guard.set_owner(owner);
+        let mut page = RecordPage::new(&mut *guard);
 
         let old_pid = pid;
         pid = page.get_next_page_id()?;
 
         f(old_pid, &mut page)?;
-        bufmgr.unpin_page(old_pid)?;
     }
     Ok(())
 }
@@ -145,33 +306,32 @@ where F: FnMut(PageId, &mut RecordPage) -> Res<()> {
 //     res
 // }
 
-pub fn create_page(mutex: &Arc<Mutex<BufMgr>>) -> Res<PageId> {
-    let mut bufmgr = mutex.lock().unwrap();
-    let (page_id, _) = bufmgr.create_page()?;
+pub fn create_page(bufmgr: &BufMgr) -> Res<PageId> {
+    let page_id = bufmgr.new_page()?.get_page_id();
     Ok(page_id)
 }
 
 pub fn run_hfilemgr() -> Res<()> {
-    let name = "nano-hfilemgr.db";
     // // let diskmgr = DiskMgr::open_db(name)?;
     // // let bufmgr = BufMgr::new(10, diskmgr);
     // // let bufmgr = Arc::new(Mutex::new(bufmgr));
     // let mut hfilemgr = HFileMgr::build(bufmgr)?;
+    let name = "nano-hfilemgr.db";
     let mut hfilemgr = HFileMgr::build_default(name)?;
 
     println!("create heap file");
     let mut file_a = hfilemgr.create_file("file_a")?;
     println!("eno={:}", file_a.get_entry_no().value);
-    let mut data: [u8; PAGE_RECORD_BYTE] = [0; PAGE_RECORD_BYTE];
-    for i in 0..10 {
-        data[i] = 10 + i as u8 + 1;
+    let mut data = [0; 10];
+    for (i, x) in data.iter_mut().enumerate() {
+        *x = 10 + i as u8 + 1;
     }
     for _ in 0..3 {
-        file_a.insert_record(data)?;
+        file_a.insert_record(&data)?;
     }
-    let rid = file_a.insert_record(data)?;
+    let rid = file_a.insert_record(&data)?;
     for _ in 0..3 {
-        file_a.insert_record(data)?;
+        file_a.insert_record(&data)?;
     }    
     println!("rid=({}, {})", rid.page_id, rid.slot_no.value);
 
@@ -186,23 +346,340 @@ pub fn run_hfilemgr() -> Res<()> {
     assert!(a.is_err());
 
     println!("insert other data");
-    let mut data: [u8; PAGE_RECORD_BYTE] = [0; PAGE_RECORD_BYTE];
-    for i in 0..10 {
-        data[i] = i as u8 + 1;
+    // records may differ in length
+    let mut data = [0; 20];
+    for (i, x) in data.iter_mut().enumerate() {
+        *x = i as u8 + 1;
     }
-    let rid = file_a.insert_record(data)?;
+    let rid = file_a.insert_record(&data)?;
     println!("rid=({}, {})", rid.page_id, rid.slot_no.value);
     let rec = file_a.get_record(rid)?;
+    assert_eq!(20, rec.len());
     assert_eq!(3, rec[2]);
     println!("rec={:?}", rec);
 
+    println!("update record in place");
+    file_a.update_record(rid, &[7; 10])?;
+    assert_eq!(vec![7; 10], file_a.get_record(rid)?);
+
+    println!("update record with data larger than free space of page");
+    let large = vec![8; 900];
+    file_a.update_record(rid, &large)?;
+    assert_eq!(large, file_a.get_record(rid)?);
+    file_a.update_record(rid, &[9; 950])?;
+    assert_eq!(vec![9; 950], file_a.get_record(rid)?);
+
     println!("print all");
     let mutex = Arc::new(Mutex::new(file_a));
-    let mut it = RawFileScan::new(mutex);
-    while let Some((rid, rec)) = it.get_next()?{
-        println!("({},{}): {:?}", rid.page_id, rid.slot_no.value, rec)
+    let mut num_records = 0;
+    for res in RawFileScan::new(mutex) {
+        let (rid, rec) = res?;
+        println!("({},{}): {:?}", rid.page_id, rid.slot_no.value, rec.len());
+        num_records += 1;
     }
+    // relocated record is visited once through its original record id
+    assert_eq!(7, num_records);
+
+    println!("rename file");
+    hfilemgr.rename_file("file_a", "file_b")?;
+    let mut file_b = hfilemgr.open("file_b")?;
+    assert_eq!(vec![9; 950], file_b.get_record(rid)?);
+    assert!(hfilemgr.rename_file("file_a", "file_c").is_err());
+
+    println!("vacuum file");
+    let stats = file_b.vacuum()?;
+    println!("reclaimed {} bytes and {} pages", stats.bytes_reclaimed, stats.pages_reclaimed);
+    let rid = stats.moved.iter().find(|(old, _)| *old == rid).map_or(rid, |(_, new)| *new);
+    assert_eq!(vec![9; 950], file_b.get_record(rid)?);
+
+    println!("truncate file");
+    file_b.truncate()?;
+    file_b.with_record_pages(|_, page| {
+        assert_eq!(0, page.get_num_slots()?);
+        Ok(())
+    })?;
+
+    println!("drop file");
+    let entry_no = file_b.get_entry_no();
+    hfilemgr.drop_file("file_b")?;
+    assert!(hfilemgr.drop_file("file_b").is_err());
+    // entry of dropped file is reused
+    let file_c = hfilemgr.create_file("file_c")?;
+    assert_eq!(entry_no, file_c.get_entry_no());
+    hfilemgr.close()?;
 
     std::fs::remove_file(name).unwrap();
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::diskmgr::MemPageStore;
+    use crate::page::PAGE_BYTE;
+
+    use super::*;
+
+    // file manager on memory. buffer pool is returned too, so that tests can
+    // see which pages are allocated.
+    fn build_mem_filemgr() -> Res<(Arc<BufMgr>, HFileMgr)> {
+        let bufmgr = Arc::new(BufMgr::new(10, MemPageStore::new(PAGE_BYTE)));
+        let filemgr = HFileMgr::build(bufmgr.clone())?;
+        Ok((bufmgr, filemgr))
+    }
+
+    #[test]
+    fn test_hfilemgr() {
+        run_hfilemgr().unwrap();
+    }
+
+    #[test]
+    fn test_overflow() -> Res<()> {
+        let (bufmgr, mut filemgr) = build_mem_filemgr()?;
+        let mut file = filemgr.create_file("file0")?;
+        file.insert_record(&[1; 10])?;
+        let data: Vec<u8> = (0..5000).map(|i| (i % 251) as u8).collect();
+        let rid = file.insert_record(&data)?;
+        assert_eq!(data, file.get_record(rid)?);
+
+        file.update_record(rid, &[3; 3000])?;
+        assert_eq!(vec![3; 3000], file.get_record(rid)?);
+        file.update_record(rid, &[4; 20])?;
+        assert_eq!(vec![4; 20], file.get_record(rid)?);
+        file.update_record(rid, &data)?;
+
+        let file = Arc::new(Mutex::new(file));
+        let mut scan = RawFileScan::new(file.clone());
+        let mut lens = Vec::new();
+        while let Some((_, rec)) = scan.get_next()? {
+            lens.push(rec.len());
+        }
+        assert_eq!(vec![10, 5000], lens);
+
+        // overflow pages of deleted record are reused after checkpoint
+        let page_id = create_page(&bufmgr)?;
+        bufmgr.free_page(page_id)?;
+        let mut file = file.lock().unwrap();
+        file.delete_record(rid)?;
+        assert!(file.get_record(rid).is_err());
+        bufmgr.flush_all()?;
+        let rid = file.insert_record(&data)?;
+        assert_eq!(data, file.get_record(rid)?);
+        assert_eq!(page_id, create_page(&bufmgr)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_drop_file() -> Res<()> {
+        let (bufmgr, mut filemgr) = build_mem_filemgr()?;
+        let fill = |file: &mut HeapFile| -> Res<()> {
+            for i in 0..50 {
+                file.insert_record(&[i as u8; 100])?;
+            }
+            file.insert_record(&[1; 3000])?;
+            Ok(())
+        };
+        fill(&mut filemgr.create_file("file0")?)?;
+        let page_id = create_page(&bufmgr)?;
+        bufmgr.free_page(page_id)?;
+
+        // same file built again uses only pages released by drop
+        filemgr.drop_file("file0")?;
+        assert!(filemgr.rename_file("file0", "file1").is_err());
+        bufmgr.flush_all()?;
+        fill(&mut filemgr.create_file("file0")?)?;
+        assert_eq!(page_id, create_page(&bufmgr)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_directory() -> Res<()> {
+        let (bufmgr, mut filemgr) = build_mem_filemgr()?;
+        filemgr.create_file("file10")?;
+        assert!(filemgr.find_file("file1")?.is_none());
+        assert!(filemgr.create_file("file1").is_ok());
+
+        // name longer than directory entry is never found, and can't be given
+        let long_name = "f".repeat(21);
+        assert!(!filemgr.contains_file(&long_name)?);
+        assert!(filemgr.create_file(&long_name).is_err());
+        assert!(filemgr.rename_file("file1", &long_name).is_err());
+        assert!(filemgr.find_file("file1")?.is_some());
+
+        // directory spans pages beyond the first one
+        for i in 0..2000 {
+            let mut file = filemgr.create_file(&format!("rel{}", i))?;
+            file.insert_record(&[i as u8; 4])?;
+        }
+        let mut filemgr = HFileMgr::build(bufmgr)?;
+        for i in [0, 999, 1999] {
+            let name = format!("rel{}", i);
+            let entry_no = filemgr.find_file(&name)?.unwrap();
+            assert_eq!(name, filemgr.get_file_name(entry_no)?);
+            let file = filemgr.open(&name)?;
+            let mut recs = Vec::new();
+            file.with_record_pages(|_, page| {
+                for slot in 0..page.get_num_slots()? {
+                    recs.push(page.get_slot(SlotNo::new(slot))?.to_vec());
+                }
+                Ok(())
+            })?;
+            assert_eq!(vec![vec![i as u8; 4]], recs);
+        }
+
+        // entry of dropped file is reused
+        let entry_no = filemgr.find_file("rel999")?.unwrap();
+        filemgr.drop_file("rel999")?;
+        assert!(filemgr.find_file("rel999")?.is_none());
+        filemgr.create_file("rel2000")?;
+        assert_eq!(Some(entry_no), filemgr.find_file("rel2000")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_vacuum() -> Res<()> {
+        let (bufmgr, mut filemgr) = build_mem_filemgr()?;
+        let mut file = filemgr.create_file("file0")?;
+        let mut rids = Vec::new();
+        for i in 0..200 {
+            rids.push(file.insert_record(&[i as u8; 100])?);
+        }
+        // relocated record and record in overflow pages are kept by vacuum
+        let large: Vec<u8> = (0..3000).map(|i| (i % 251) as u8).collect();
+        file.update_record(rids[0], &[200; 500])?;
+        file.update_record(rids[199], &large)?;
+        for (i, rid) in rids.iter().enumerate() {
+            if i % 10 != 0 && i != 199 {
+                file.delete_record(*rid)?;
+            }
+        }
+
+        let stats = file.vacuum()?;
+        assert!(stats.pages_reclaimed > 15);
+        assert!(stats.bytes_reclaimed > 170 * 100);
+        assert!(!stats.moved.is_empty());
+        // record ids taken before vacuum are valid through the map
+        let new_rid = |rid: RecordId| stats.moved.iter().find(|(old, _)| *old == rid).map_or(rid, |(_, new)| *new);
+        for i in (10..199).step_by(10) {
+            assert_eq!(vec![i as u8; 100], file.get_record(new_rid(rids[i]))?);
+        }
+        assert_eq!(vec![200; 500], file.get_record(new_rid(rids[0]))?);
+        assert_eq!(large, file.get_record(new_rid(rids[199]))?);
+        assert_eq!(VacuumStats::default(), file.vacuum()?);
+        let file = Arc::new(Mutex::new(file));
+        let mut scan = RawFileScan::new(file.clone());
+        let mut recs = Vec::new();
+        while let Some((rid, rec)) = scan.get_next()? {
+            assert_eq!(rec, file.lock().unwrap().get_record(rid)?);
+            recs.push(rec);
+        }
+        recs.sort();
+        let mut expected: Vec<Vec<u8>> = (10..200).step_by(10).map(|i| vec![i as u8; 100]).collect();
+        expected.push(vec![200; 500]);
+        expected.push(large);
+        expected.sort();
+        assert_eq!(expected, recs);
+
+        // freed pages are reused by other files after checkpoint
+        let page_id = create_page(&bufmgr)?;
+        bufmgr.free_page(page_id)?;
+        bufmgr.flush_all()?;
+        let mut other = filemgr.create_file("file1")?;
+        assert_eq!(page_id, other.get_header_free_page_id()?);
+        other.insert_record(&[1; 10])?;
+
+        // free-space map is rebuilt for inserts after vacuum
+        let mut file = file.lock().unwrap();
+        for i in 0..100 {
+            file.insert_record(&[i as u8; 100])?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_bulk_load() -> Res<()> {
+        let (bufmgr, mut filemgr) = build_mem_filemgr()?;
+        let mut file = filemgr.create_file("file0")?;
+        let rid = file.insert_record(&[0; 100])?;
+        let large = vec![1; 3000];
+        let records = (0..500).map(|i| if i == 250 { large.clone() } else { vec![i as u8; 100] });
+        assert_eq!(500, file.bulk_load(records)?);
+
+        // records follow the existing one in the order of loading, filling each page
+        let file = Arc::new(Mutex::new(file));
+        let mut scan = RawFileScan::new(file.clone());
+        let mut recs = Vec::new();
+        while let Some((_, rec)) = scan.get_next()? {
+            recs.push(rec);
+        }
+        assert_eq!(501, recs.len());
+        assert_eq!(vec![0; 100], recs[0]);
+        assert_eq!(large, recs[251]);
+        assert_eq!(vec![255; 100], recs[256]);
+        let mut file = file.lock().unwrap();
+        let mut num_pages = 0;
+        file.with_record_pages(|_, page| {
+            num_pages += 1;
+            assert!(page.get_free_space()? < 108 || page.get_next_page_id()? == 0);
+            Ok(())
+        })?;
+        assert_eq!(501usize.div_ceil(9), num_pages);
+        assert_eq!(vec![0; 100], file.get_record(rid)?);
+
+        // free-space map knows pages appended by the load
+        file.delete_record(rid)?;
+        assert_eq!(rid.page_id, file.insert_record(&[2; 100])?.page_id);
+        assert!(bufmgr.get_frames().iter().all(|frame| frame.pin_count == 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_free_space_map() -> Res<()> {
+        let (bufmgr, mut filemgr) = build_mem_filemgr()?;
+        let mut file = filemgr.create_file("file0")?;
+        let mut rids = Vec::new();
+        for i in 0..200 {
+            rids.push(file.insert_record(&[i as u8; 100])?);
+        }
+        let count_pages = |file: &HeapFile| -> Res<usize> {
+            let mut n = 0;
+            file.with_record_pages(|_, _| {
+                n += 1;
+                Ok(())
+            })?;
+            Ok(n)
+        };
+        let num_pages = count_pages(&file)?;
+        assert!(num_pages > 20);
+
+        // insert goes to the page with room instead of the tail
+        file.delete_record(rids[3])?;
+        let rid = file.insert_record(&[7; 100])?;
+        assert_eq!(rids[3].page_id, rid.page_id);
+        let rid = file.insert_record(&[8; 100])?;
+        assert_eq!(rids[199].page_id, rid.page_id);
+
+        file.truncate()?;
+        let mut rids = Vec::new();
+        for i in 0..200 {
+            rids.push(file.insert_record(&[i as u8; 100])?);
+        }
+        assert_eq!(num_pages, count_pages(&file)?);
+
+        // crash may leave entry of page which didn't get linked in the map
+        let last_page_id = rids[199].page_id;
+        file.delete_record(rids[199])?;
+        let prev_page_id = file.with_record_page_read(last_page_id, |page| page.get_prev_page_id())?;
+        file.with_record_page(prev_page_id, |page| page.set_next_page_id(0))?;
+        let mut filemgr = HFileMgr::build(bufmgr)?;
+        let mut file = filemgr.open("file0")?;
+        let rid = file.insert_record(&[9; 100])?;
+        assert_ne!(last_page_id, rid.page_id);
+        let mut page_ids = Vec::new();
+        file.with_record_pages(|page_id, _| {
+            page_ids.push(page_id);
+            Ok(())
+        })?;
+        assert!(page_ids.contains(&rid.page_id));
+        assert!(!page_ids.contains(&last_page_id));
+        Ok(())
+    }
+}
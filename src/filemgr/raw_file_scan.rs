@@ -1,11 +1,15 @@
 use std::sync::{Arc, Mutex};
 
 use crate::types::*;
-use super::{PAGE_RECORD_BYTE, RecordPage, SlotNo, RecordId, HeapFile};
+use crate::page::Page;
+use crate::bufmgr::{BufferRing, ReadAhead};
+use super::{RecordPage, SlotNo, RecordId, HeapFile};
 
 pub struct RawFileScan {
     heap_file: Arc<Mutex<HeapFile>>,
     status: ScanStatus,
+    ring: Option<Arc<BufferRing>>,
+    read_ahead: Option<ReadAhead>,
 }
 
 enum ScanStatus {
@@ -19,13 +23,35 @@ impl RawFileScan {
         RawFileScan {
             heap_file,
             status: ScanStatus::Starting,
+            ring: None,
+            read_ahead: None,
+        }
+    }
+
+    // for full scan of large file. following `num_pages` pages are loaded
+    // in background, and pages are read into a ring of frames so that hot
+    // pages of other files stay in buffer pool.
+    pub fn with_read_ahead(heap_file: Arc<Mutex<HeapFile>>, num_pages: usize) -> RawFileScan {
+        let bufmgr = heap_file.lock().unwrap().get_bufmgr();
+        // scan position and pages read ahead must fit in the ring
+        let ring = Arc::new(BufferRing::new(2 * num_pages + 2));
+        let next_page = |page: &Page| RecordPage::new(page).get_next_page_id();
+        let read_ahead = ReadAhead::start(&bufmgr, Some(ring.clone()), num_pages, next_page);
+        RawFileScan {
+            heap_file,
+            status: ScanStatus::Starting,
+            ring: Some(ring),
+            read_ahead: Some(read_ahead),
         }
     }
 
     pub fn with_record_page<F, T>(&self, page_id: PageId, f: F) -> Res<T> 
-    where F: FnOnce(&mut RecordPage) -> Res<T> {
+    where F: FnOnce(&RecordPage<&Page>) -> Res<T> {
         let hf = self.heap_file.lock().unwrap();
-        hf.with_record_page(page_id, f)
+        match &self.ring {
+            Some(ring) => hf.with_record_page_read_in(page_id, ring, f),
+            None => hf.with_record_page_read(page_id, f),
+        }
     }
 
     pub fn peer_next_rid(&mut self) -> Res<Option<RecordId>> {
@@ -38,7 +64,7 @@ impl RawFileScan {
         }
     }
 
-    pub fn get_next(&mut self) -> Res<Option<(RecordId, [u8; PAGE_RECORD_BYTE])>> {
+    pub fn get_next(&mut self) -> Res<Option<(RecordId, Vec<u8>)>> {
         let rid = match self.status {
             ScanStatus::Starting => {
                 if let Some(rid) = self.init_rid()? {
@@ -63,8 +89,17 @@ impl RawFileScan {
         match rid {
             None => Ok(None),
             Some(rid) => {
-                let hf = self.heap_file.lock().unwrap();
-                let rec = hf.get_record(rid)?;
+                let rec = self.with_record_page(rid.page_id, |page| {
+                    if page.get_forward(rid.slot_no)?.is_some() || page.get_overflow(rid.slot_no)?.is_some() {
+                        return Ok(None);
+                    }
+                    Ok(Some(page.get_slot(rid.slot_no)?.to_vec()))
+                })?;
+                let rec = match rec {
+                    Some(rec) => rec,
+                    // relocated or overflowed record is read through heap file with its original record id
+                    None => self.heap_file.lock().unwrap().get_record(rid)?,
+                };
                 Ok(Some((rid, rec)))
             }
         }
@@ -73,81 +108,108 @@ impl RawFileScan {
     fn init_rid(&mut self) -> Res<Option<RecordId>> {
         let page_id = {
             let hf = self.heap_file.lock().unwrap();
-            let page_id = hf.with_header_page(|page| {
+            hf.with_header_page_read(|page| {
                 page.get_head_free_page_id(hf.get_entry_no())
-            })?;
-            page_id
+            })?
         };
+        self.advance(page_id);
         self.next(page_id, None)
     }
 
+    fn advance(&self, page_id: PageId) {
+        if let Some(read_ahead) = &self.read_ahead {
+            read_ahead.advance(page_id);
+        }
+    }
+
     fn next_rid(&mut self, rid: RecordId) -> Res<Option<RecordId>> {
         self.next(rid.page_id, Some(rid.slot_no))
     }
 
+    // pages without live record are skipped in a loop, so long run of empty
+    // pages doesn't grow the stack
     fn next(&mut self, page_id: PageId, slot_no: Option<SlotNo>) -> Res<Option<RecordId>> {
         enum R {
             RecordId(RecordId),
             PageId(PageId),
             None,
         }
+        let mut page_id = page_id;
         let mut slot_no = match slot_no {
             Some(slot_no) => slot_no.value + 1,
             None => 0,
         };
 
-        let r = self.with_record_page(page_id,
-            |page| {
-                let num_slots = page.get_num_slots()?;
-                while slot_no < num_slots {
-                    if !page.is_free_slot(SlotNo::new(slot_no))? {
-                        return Ok(R::RecordId(RecordId {
-                            page_id,
-                            slot_no: SlotNo::new(slot_no),
-                        }));
+        loop {
+            let r = self.with_record_page(page_id,
+                |page| {
+                    let num_slots = page.get_num_slots()?;
+                    while slot_no < num_slots {
+                        let sno = SlotNo::new(slot_no);
+                        if !page.is_free_slot(sno)? && !page.is_moved_slot(sno)? {
+                            return Ok(R::RecordId(RecordId {
+                                page_id,
+                                slot_no: SlotNo::new(slot_no),
+                            }));
+                        }
+                        slot_no += 1;
+                    }
+                    let next_page_id = page.get_next_page_id()?;
+                    if next_page_id == 0 {
+                        Ok(R::None)
+                    } else {
+                        Ok(R::PageId(next_page_id))
                     }
-                    slot_no += 1;
                 }
-                let next_page_id = page.get_next_page_id()?;
-                if next_page_id == 0 {
-                    Ok(R::None)
-                } else {
-                    Ok(R::PageId(next_page_id))
+            )?;
+            match r {
+                R::RecordId(rid) => return Ok(Some(rid)),
+                R::PageId(pid) => {
+                    self.advance(pid);
+                    page_id = pid;
+                    slot_no = 0;
                 }
+                R::None => return Ok(None),
             }
-        )?;
-        match r {
-            R::RecordId(rid) => Ok(Some(rid)),
-            R::PageId(pid) => self.next(pid, None),
-            R::None => Ok(None),
         }
-
-        
     }
 }
 
 
+// records until the end of file. iteration stops after the first error.
+impl Iterator for RawFileScan {
+    type Item = Res<(RecordId, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.get_next() {
+            Ok(res) => res.map(Ok),
+            Err(e) => {
+                self.status = ScanStatus::Finished;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
-    use crate::{diskmgr::DiskMgr, bufmgr::BufMgr, filemgr::HFileMgr};
+    use crate::{diskmgr::MemPageStore, bufmgr::BufMgr, filemgr::HFileMgr, page::PAGE_BYTE};
 
     use super::*;
 
     #[test]
     fn test_merge_sort() -> Res<()> {
-        let name = "nano-raw-file-scan.db";
-        let diskmgr = DiskMgr::open_db(name)?;
-        let bufmgr = BufMgr::new(10, diskmgr);
-        let bufmgr = Arc::new(Mutex::new(bufmgr));
+        let bufmgr = BufMgr::new(10, MemPageStore::new(PAGE_BYTE));
+        let bufmgr = Arc::new(bufmgr);
         let mut filemgr = HFileMgr::build(bufmgr)?;
     
         let mut file = filemgr.create_file("file0")?;
 
         for i in 0..10 {
-            let mut data = [0; PAGE_RECORD_BYTE];
+            let mut data = [0; 128];
             data[1] = i;
-            file.insert_record(data)?;
+            file.insert_record(&data)?;
         }
 
         let file = Arc::new(Mutex::new(file));
@@ -163,4 +225,62 @@ mod tests {
         Ok(())
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_concurrent_scan() -> Res<()> {
+        let bufmgr = Arc::new(BufMgr::new(16, MemPageStore::new(PAGE_BYTE)));
+        let mut filemgr = HFileMgr::build(bufmgr)?;
+        let mut files = Vec::new();
+        for name in ["file0", "file1", "file2"] {
+            let mut file = filemgr.create_file(name)?;
+            for i in 0..40 {
+                let mut data = [0; 128];
+                data[0] = i;
+                file.insert_record(&data)?;
+            }
+            files.push(Arc::new(Mutex::new(file)));
+        }
+
+        let handles: Vec<_> = files.into_iter().map(|file| {
+            std::thread::spawn(move || -> Res<usize> {
+                let mut n = 0;
+                for _ in 0..10 {
+                    let mut scan = RawFileScan::new(file.clone());
+                    while let Some((_, rec)) = scan.get_next()? {
+                        assert!(rec[0] < 40);
+                        n += 1;
+                    }
+                }
+                Ok(n)
+            })
+        }).collect();
+        for h in handles {
+            assert_eq!(400, h.join().unwrap()?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_ahead_scan() -> Res<()> {
+        let bufmgr = Arc::new(BufMgr::new(8, MemPageStore::new(PAGE_BYTE)));
+        let mut filemgr = HFileMgr::build(bufmgr)?;
+        let mut file = filemgr.create_file("file0")?;
+        // file is larger than buffer pool
+        for i in 0..100 {
+            let mut data = [0; 128];
+            data[0] = i;
+            file.insert_record(&data)?;
+        }
+
+        let file = Arc::new(Mutex::new(file));
+        for num_pages in [1, 2] {
+            let mut scan = RawFileScan::with_read_ahead(file.clone(), num_pages);
+            let mut i = 0;
+            while let Some((_, rec)) = scan.get_next()? {
+                assert_eq!(i, rec[0]);
+                i += 1;
+            }
+            assert_eq!(100, i);
+        }
+        Ok(())
+    }
+}
@@ -8,13 +8,11 @@ mod relop;
 mod parser;
 mod nanodb;
 
-use env_logger;
-
-use crate::diskmgr::run_diskmgr;
-use crate::bufmgr::run_bufmgr;
+use crate::diskmgr::{run_diskmgr, run_diskmgr_grow, run_diskmgr_map_pages, run_diskmgr_super_block, run_diskmgr_checksum, run_diskmgr_page_byte};
+use crate::bufmgr::{run_bufmgr, run_bufmgr_fault, run_bufmgr_eviction, run_bufmgr_page_guard, run_bufmgr_concurrent, run_bufmgr_background_writer, run_bufmgr_shutdown, run_bufmgr_ring, run_replacer_trace, ReplacerKind};
 use crate::filemgr::run_hfilemgr;
 use crate::relop::{run_relmgr, run_relmgr_projection, run_merge_sort};
-use crate::nanodb::run_nanodb;
+use crate::nanodb::{run_nanodb, run_nanodb_reopen, run_nanodb_page_byte, run_nanodb_fault_injection, run_nanodb_buf_stats, run_nanodb_long_value, run_nanodb_bulk_load};
 
 fn main() {
     env_logger::init();
@@ -23,8 +21,34 @@ fn main() {
     println!("nanodb start");
     if i == 0 {
         run_diskmgr().unwrap();
+    } else if i == 2 {
+        run_diskmgr_grow().unwrap();
+    } else if i == 3 {
+        run_diskmgr_map_pages().unwrap();
+    } else if i == 8 {
+        run_diskmgr_super_block().unwrap();
+    } else if i == 9 {
+        run_diskmgr_checksum().unwrap();
+    } else if i == 12 {
+        run_diskmgr_page_byte().unwrap();
     } else if i == 1 {
         run_bufmgr().unwrap();
+    } else if i == 15 {
+        run_bufmgr_fault().unwrap();
+    } else if i == 16 {
+        run_bufmgr_eviction(ReplacerKind::Clock).unwrap();
+    } else if i == 17 {
+        run_replacer_trace();
+    } else if i == 18 {
+        run_bufmgr_page_guard().unwrap();
+    } else if i == 19 {
+        run_bufmgr_concurrent().unwrap();
+    } else if i == 20 {
+        run_bufmgr_background_writer().unwrap();
+    } else if i == 21 {
+        run_bufmgr_shutdown().unwrap();
+    } else if i == 22 {
+        run_bufmgr_ring().unwrap();
     } else if i==4 {
         run_hfilemgr().unwrap();
     } else if i==5 {
@@ -33,6 +57,18 @@ fn main() {
         run_relmgr_projection().unwrap();
     } else if i==7 {
         run_merge_sort().unwrap();
+    } else if i==11 {
+        run_nanodb_reopen().unwrap();
+    } else if i==13 {
+        run_nanodb_page_byte().unwrap();
+    } else if i==14 {
+        run_nanodb_fault_injection(0).unwrap();
+    } else if i==23 {
+        run_nanodb_buf_stats().unwrap();
+    } else if i==24 {
+        run_nanodb_long_value().unwrap();
+    } else if i==25 {
+        run_nanodb_bulk_load().unwrap();
     } else if i==10 {
         match run_nanodb() {
             Ok(_) => {},
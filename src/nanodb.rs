@@ -1,47 +1,82 @@
 use std::sync::{Arc, Mutex};
 
-use crate::relop::{AttributeType, Projection, FileScan};
+use crate::relop::{AttributeType, Projection, FileScan, Executor, ValuesScan};
 use crate::types::*;
 
-use crate::diskmgr::DiskMgr;
-use crate::bufmgr::BufMgr;
-use crate::filemgr::{HFileMgr, HeapFile};
-use crate::relop::{schema::Schema, Record};
+use crate::diskmgr::{DiskMgr, PageStore, MemPageStore, FaultPageStore, FaultConfig};
+use crate::page::PAGE_BYTE;
+use crate::bufmgr::{ReplacerKind, BackgroundWriterConfig, BufMgrStats};
+use crate::filemgr::{HFileMgr, HeapFile, DEFAULT_MAX_BUFSIZE};
+use crate::relop::{schema::Schema, Tuple};
 
 use crate::parser::*;
 
 const CATALOG_ATTRIBUTE_CAT: &str = "attr_";
 
+// system tables. contents are taken from buffer pool each time they are selected.
+const SYSTEM_BUF_STAT: &str = "buf_stat";
+const SYSTEM_BUF_FRAME: &str = "buf_frame";
+
 pub struct NanoDb {
     filemgr: HFileMgr,
 
     catalog_attr_cat_file: Arc<Mutex<HeapFile>>,
-    catalog_attr_cat_schema: Schema,
+    catalog_attr_cat_schema: Arc<Schema>,
 }
 
 impl NanoDb {
     pub fn build(name: &str) -> Res<NanoDb> {
         let diskmgr = DiskMgr::open_db(name)?;
-        let bufmgr = BufMgr::new(10, diskmgr);
-        let bufmgr = Arc::new(Mutex::new(bufmgr));
-        let mut filemgr = HFileMgr::build(bufmgr)?;
+        Self::build_on(diskmgr)
+    }
+
+    // page size is chosen when the database is created
+    pub fn build_with_page_byte(name: &str, page_byte: usize) -> Res<NanoDb> {
+        let diskmgr = DiskMgr::open_db_with_page_byte(name, page_byte)?;
+        Self::build_on(diskmgr)
+    }
+
+    // eviction policy and size of buffer pool are chosen each time database is opened
+    pub fn build_with_replacer(name: &str, kind: ReplacerKind, max_bufsize: usize) -> Res<NanoDb> {
+        let diskmgr = DiskMgr::open_db(name)?;
+        Self::build_on_with_replacer(diskmgr, kind, max_bufsize)
+    }
+
+    // database on memory. contents are lost when dropped.
+    pub fn build_in_memory() -> Res<NanoDb> {
+        Self::build_on(MemPageStore::new(PAGE_BYTE))
+    }
+
+    pub fn build_on(store: impl PageStore + 'static) -> Res<NanoDb> {
+        Self::build_on_with_replacer(store, ReplacerKind::Lru, DEFAULT_MAX_BUFSIZE)
+    }
+
+    pub fn build_on_with_replacer(store: impl PageStore + 'static, kind: ReplacerKind, max_bufsize: usize) -> Res<NanoDb> {
+        let mut filemgr = HFileMgr::build_on_with_replacer(store, kind, max_bufsize)?;
 
         let catalog_attr_cat_file = filemgr.open(CATALOG_ATTRIBUTE_CAT)?;
         let catalog_attr_cat_file = Arc::new(Mutex::new(catalog_attr_cat_file));
-        let catalog_attr_cat_schema = Schema::build(vec![
+        let catalog_attr_cat_schema = Arc::new(Schema::build(vec![
             ("aname".to_string(), AttributeType::Varchar(10) ),
             ("rname".to_string(), AttributeType::Varchar(10) ),
             ("type_".to_string(), AttributeType::Varchar(10) ),
             ("size".to_string(), AttributeType::Int ),
             ("posit".to_string(), AttributeType::Int ),
-        ]);
+        ]));
         Ok(NanoDb{ filemgr, catalog_attr_cat_file, catalog_attr_cat_schema })
     }
 
     pub fn init(&mut self) -> Res<()> {
         // let a = &self.catalog_attr_cat_schema;
         // self.insert_into_schema(a, CATALOG_ATTRIBUTE_CAT)?;
-        // FIXME: check existance before write
+        match self.open_relation(CATALOG_ATTRIBUTE_CAT) {
+            Ok(_) => {
+                log::debug!("catalog already exists");
+                return Ok(());
+            }
+            Err(Error::RelationNotFound { .. }) => {}
+            Err(e) => return Err(e),
+        }
         let flen = self.catalog_attr_cat_schema.len();
         let rel_name = CATALOG_ATTRIBUTE_CAT;
         for fno in 0..flen {
@@ -56,6 +91,65 @@ impl NanoDb {
         Ok(())
     }
 
+    pub fn get_bufmgr_stats(&self) -> BufMgrStats {
+        self.filemgr.get_bufmgr_stats()
+    }
+
+    // system tables are built from a snapshot of buffer pool and aren't stored in
+    // heap files, so reading them doesn't change what they report.
+    // names of owner files are looked up after the snapshot is taken.
+    fn scan_system_table(&self, name: &str) -> Res<Option<ValuesScan>> {
+        let to_int = |x: u64| i32::try_from(x).unwrap_or(i32::MAX);
+        let int = |name: &str| (name.to_string(), AttributeType::Int);
+        let (columns, rows) = match name {
+            SYSTEM_BUF_STAT => {
+                let stats = self.get_bufmgr_stats();
+                let columns = vec![int("hits"), int("misses"), int("evictions"), int("dirty_wr"), int("pin_waits")];
+                let rows = vec![vec![
+                    Value::Int(to_int(stats.hits)),
+                    Value::Int(to_int(stats.misses)),
+                    Value::Int(to_int(stats.evictions)),
+                    Value::Int(to_int(stats.dirty_writes)),
+                    Value::Int(to_int(stats.pin_waits)),
+                ]];
+                (columns, rows)
+            }
+            SYSTEM_BUF_FRAME => {
+                let columns = vec![int("frame_no"), int("page_id"), int("pin_count"), int("dirty"),
+                    ("owner".to_string(), AttributeType::Varchar(20))];
+                let mut rows = Vec::new();
+                for frame in self.filemgr.get_frames() {
+                    let owner = match frame.owner {
+                        Some(entry_no) => self.filemgr.get_file_name(entry_no)?,
+                        None => String::new(),
+                    };
+                    rows.push(vec![
+                        Value::Int(frame.frame_no as i32),
+                        Value::Int(frame.page_id as i32),
+                        Value::Int(frame.pin_count as i32),
+                        Value::Int(frame.dirty as i32),
+                        Value::String(owner),
+                    ]);
+                }
+                (columns, rows)
+            }
+            _ => return Ok(None),
+        };
+        let schema = Arc::new(Schema::build(columns));
+        let rows = rows.iter()
+            .map(|values| encode_values(&schema, values))
+            .collect::<Res<Vec<_>>>()?;
+        Ok(Some(ValuesScan::new(schema, rows)))
+    }
+
+    pub fn start_background_writer(&mut self, config: BackgroundWriterConfig) {
+        self.filemgr.start_background_writer(config)
+    }
+
+    pub fn close(&mut self) -> Res<()> {
+        self.filemgr.close()
+    }
+
     pub fn execute_statement(&mut self, statement: SqlStatement) -> Res<()> {
         match statement {
             SqlStatement::CreateTable(s) => {
@@ -66,8 +160,8 @@ impl NanoDb {
             SqlStatement::Select(s) => {
                 let mut it = self.execute_select(s)?;
                 log::info!("select result:");
-                while let Some((rid, rec)) = it.get_next()? {
-                    println!("{}: {}", rid, rec);
+                while let Some(tuple) = it.next_tuple()? {
+                    println!("{}: {}", tuple.get_rid().unwrap(), tuple);
                 }
                 Ok(())
             }
@@ -75,6 +169,17 @@ impl NanoDb {
     }
 
     fn execute_create_table(&mut self, statement: CreateTableStatement) -> Res<HeapFile> {
+        check_writable(&statement.table_name)?;
+        match self.open_relation(&statement.table_name) {
+            // file may be left without catalog entries by interrupted CREATE TABLE
+            Err(Error::RelationNotFound { .. }) => {
+                if self.filemgr.contains_file(&statement.table_name)? {
+                    self.filemgr.drop_file(&statement.table_name)?;
+                }
+            }
+            Err(e) => return Err(e),
+            Ok(_) => {}
+        }
         let heap_file = self.filemgr.create_file(&statement.table_name)?;
         for fno in 0..statement.columns.len() {
             let c = statement.columns.get(fno).unwrap();
@@ -87,7 +192,7 @@ impl NanoDb {
     fn insert_into_catalog_attr_type(&mut self, attr_name: &str, rel_name: &str, ty: DataType, fno: usize) -> Res<()> {
         log::debug!("insert_into_catalog_attr_type(attr_name={}, rel_name={}, fno={})",
                 attr_name, rel_name, fno);
-        let mut rec: Record = Record::new_zero(&self.catalog_attr_cat_schema);
+        let mut rec = Tuple::new_zero(self.catalog_attr_cat_schema.clone());
         rec.set_varchar_field(0, &attr_name.to_string())?;
         rec.set_varchar_field(1, &rel_name.to_string())?;
         let (ty, size) = match ty {
@@ -98,47 +203,49 @@ impl NanoDb {
         rec.set_int_field(3, size as i32)?;
         rec.set_int_field(4, fno as i32)?;
         let mut mutex = self.catalog_attr_cat_file.lock().unwrap();
-        mutex.insert_record(*rec.get_data())?;
+        mutex.insert_record(rec.get_data())?;
         Ok(())
     }
 
     fn execute_insert_into(&mut self, statement: InsertIntoStatement) -> Res<()> {
         log::debug!("execute_insert_into");
+        check_writable(&statement.table_name)?;
         let (mut file, schema) = self.open_relation(statement.table_name.as_str())?;
-        if schema.len() != statement.values.len() {
-            return Err(Error::InvalidArg { 
-                msg: format!("size mismatch between insertion values ({}) and number of field ({})",
-                statement.values.len(),
-                schema.len(),
-            )
-            })
-        }
 
         log::debug!("execute_insert_into: add values to record");
-        let mut rec: Record = Record::new_zero(&schema);
-        for fno in 0..schema.len() {
-            match statement.values.get(fno).unwrap() {
-                Value::Int(x) => {
-                    rec.set_int_field(fno, *x)?;
-                },
-                Value::String(x) => {
-                    rec.set_varchar_field(fno, x)?;
-                }
-            }
-        }
+        let data = encode_values(&schema, &statement.values)?;
 
         log::debug!("execute_insert_into: add record");
-        file.insert_record(*rec.get_data())?;
+        file.insert_record(&data)?;
         Ok(())
     }
 
+    // append rows to table filling pages sequentially. it is much faster than
+    // INSERT INTO for large imports. every row is checked before loading, so
+    // nothing is loaded if any of them is invalid.
+    pub fn bulk_load<I>(&mut self, table_name: &str, rows: I) -> Res<usize>
+    where I: IntoIterator<Item = Vec<Value>> {
+        check_writable(table_name)?;
+        let (mut file, schema) = self.open_relation(table_name)?;
+        let records = rows.into_iter()
+            .map(|values| encode_values(&schema, &values))
+            .collect::<Res<Vec<_>>>()?;
+        file.bulk_load(records)
+    }
+
     fn execute_select(&mut self, statement: SelectStatement) -> Res<Projection> {
         log::debug!("execute_select");
-        let (file, schema) = self.open_relation(&statement.table_name)?;
-        // println!("schema: {:?}", schema);
-        let file = Arc::new(Mutex::new(file));
-        let file_scan = FileScan::new(file.clone(), schema.clone());
+        let child: Box<dyn Executor> = match self.scan_system_table(&statement.table_name)? {
+            Some(scan) => Box::new(scan),
+            None => {
+                let (file, schema) = self.open_relation(&statement.table_name)?;
+                // println!("schema: {:?}", schema);
+                let file = Arc::new(Mutex::new(file));
+                Box::new(FileScan::new(file, schema))
+            }
+        };
 
+        let schema = child.get_schema();
         let mut fnos = Vec::new();
         for c in statement.columns {
             for fno in 0..schema.len() {
@@ -148,10 +255,10 @@ impl NanoDb {
                 }
             }
         }
-        Projection::build(file_scan, fnos)
+        Projection::build(child, fnos)
     }
 
-    fn open_relation(&mut self, name: &str) -> Res<(HeapFile, Schema)> {
+    fn open_relation(&mut self, name: &str) -> Res<(HeapFile, Arc<Schema>)> {
         log::debug!("open_relation");
         // let file = self.filemgr.open(CATALOG_ATTRIBUTE_CAT)?;
         let a = self.catalog_attr_cat_file.clone();
@@ -169,7 +276,7 @@ impl NanoDb {
                 buf.push((aname, attr_type, position));
             }
         }
-        if buf.len() == 0 {
+        if buf.is_empty() {
             return Err(Error::RelationNotFound { name: name.to_string() })
         }
 
@@ -181,17 +288,49 @@ impl NanoDb {
             .map(|x| (x.0.clone(), x.1.clone())).unwrap();
             xs.push(x);
         }
-        let schema = Schema::build(xs);
+        let schema = Arc::new(Schema::build(xs));
 
         let file = self.filemgr.open(name)?;
         Ok((file, schema))
     }
 }
 
+fn check_writable(table_name: &str) -> Res<()> {
+    if table_name == SYSTEM_BUF_STAT || table_name == SYSTEM_BUF_FRAME {
+        return Err(Error::InvalidArg {
+            msg: format!("system table is read-only. name={}", table_name)
+        })
+    }
+    Ok(())
+}
+
+// record bytes of values in the order of fields of schema
+fn encode_values(schema: &Arc<Schema>, values: &[Value]) -> Res<Vec<u8>> {
+    if schema.len() != values.len() {
+        return Err(Error::InvalidArg { 
+            msg: format!("size mismatch between insertion values ({}) and number of field ({})",
+            values.len(),
+            schema.len(),
+        )
+        })
+    }
+    let mut rec = Tuple::new_zero(schema.clone());
+    for (fno, value) in values.iter().enumerate() {
+        match value {
+            Value::Int(x) => {
+                rec.set_int_field(fno, *x)?;
+            },
+            Value::String(x) => {
+                rec.set_varchar_field(fno, x)?;
+            }
+        }
+    }
+    Ok(rec.into_data())
+}
+
 pub fn run_nanodb() -> Res<()> {
     log::info!("run_nanodb start");
-    let name = "nano.db";
-    let mut nanodb = NanoDb::build(name)?;
+    let mut nanodb = NanoDb::build_in_memory()?;
     nanodb.init()?;
 
     log::info!("create student table");
@@ -235,6 +374,382 @@ pub fn run_nanodb() -> Res<()> {
     let statement = SqlStatement::Select(statement);
     nanodb.execute_statement(statement)?;
 
+    nanodb.close()?;
+    Ok(())
+}
+
+pub fn run_nanodb_reopen() -> Res<()> {
+    let name = "nano-reopen.db";
+    {
+        let mut nanodb = NanoDb::build(name)?;
+        nanodb.start_background_writer(BackgroundWriterConfig::default());
+        nanodb.init()?;
+        let statement = CreateTableStatement {
+            table_name: "student".to_string(),
+            columns: vec![
+                ColumnDef { name: "id".to_string(), data_type: DataType::Int},
+                ColumnDef { name: "name".to_string(), data_type: DataType::Varchar(10)},
+            ]};
+        nanodb.execute_statement(SqlStatement::CreateTable(statement))?;
+        for i in 0..5 {
+            let statement = InsertIntoStatement {
+                table_name: "student".to_string(),
+                values: vec![Value::Int(i), Value::String(format!("MyName{}", i))]
+            };
+            nanodb.execute_statement(SqlStatement::InsertInto(statement))?;
+        }
+        nanodb.close()?;
+    }
+
+    let mut nanodb = NanoDb::build_with_replacer(name, ReplacerKind::TwoQ, 32)?;
+    nanodb.init()?;
+    let count_catalog = |db: &mut NanoDb| -> Res<usize> {
+        let mut scan = FileScan::new(db.catalog_attr_cat_file.clone(), db.catalog_attr_cat_schema.clone());
+        let mut n = 0;
+        while scan.get_next()?.is_some() {
+            n += 1;
+        }
+        Ok(n)
+    };
+    // 5 rows for attr_ and 2 rows for student
+    assert_eq!(7, count_catalog(&mut nanodb)?);
+
+    let (file, schema) = nanodb.open_relation("student")?;
+    assert_eq!(2, schema.len());
+    let mut scan = FileScan::new(Arc::new(Mutex::new(file)), schema);
+    let mut ids = Vec::new();
+    while let Some((_, rec)) = scan.get_next()? {
+        ids.push(rec.get_int_field(0).unwrap());
+        assert_eq!(format!("MyName{}", rec.get_int_field(0).unwrap()), rec.get_varchar_field(1).unwrap());
+    }
+    assert_eq!(vec![0, 1, 2, 3, 4], ids);
+    assert!(nanodb.execute_create_table(CreateTableStatement {
+        table_name: "student".to_string(),
+        columns: vec![],
+    }).is_err());
+    nanodb.close()?;
+
     std::fs::remove_file(name).unwrap();
     Ok(())
+}
+
+pub fn run_nanodb_page_byte() -> Res<()> {
+    let name = "nano-page-byte.db";
+    let page_byte = 8192;
+    let num_rows = 2000;
+    {
+        let mut nanodb = NanoDb::build_with_page_byte(name, page_byte)?;
+        nanodb.init()?;
+        let statement = CreateTableStatement {
+            table_name: "item".to_string(),
+            columns: vec![ColumnDef { name: "id".to_string(), data_type: DataType::Int}],
+        };
+        nanodb.execute_statement(SqlStatement::CreateTable(statement))?;
+        for i in 0..num_rows {
+            let statement = InsertIntoStatement {
+                table_name: "item".to_string(),
+                values: vec![Value::Int(i)],
+            };
+            nanodb.execute_statement(SqlStatement::InsertInto(statement))?;
+        }
+        nanodb.close()?;
+    }
+
+    let mut nanodb = NanoDb::build(name)?;
+    assert_eq!(page_byte, nanodb.filemgr.get_page_byte());
+    let (file, schema) = nanodb.open_relation("item")?;
+    let mut scan = FileScan::new(Arc::new(Mutex::new(file)), schema);
+    let mut pids = Vec::new();
+    let mut count = 0;
+    while let Some((rid, rec)) = scan.get_next()? {
+        assert_eq!(count, rec.get_int_field(0).unwrap());
+        if !pids.contains(&rid.page_id) {
+            pids.push(rid.page_id);
+        }
+        count += 1;
+    }
+    assert_eq!(num_rows, count);
+    // 510 records and their slots fit in one 8KiB page. 4-byte record occupies 8 bytes.
+    assert_eq!(4, pids.len());
+    nanodb.close()?;
+
+    std::fs::remove_file(name).unwrap();
+    Ok(())
+}
+
+// run workload on a store injecting faults, then reopen the database and check
+// that every row checkpointed by `close` survived and no unknown row appeared.
+// database is reopened and crashed again in each round.
+pub fn run_nanodb_fault_injection(seed: u64) -> Res<()> {
+    let name = format!("nano-fault-{}.db", seed);
+    let num_rounds = 3;
+    let num_rows = 60;
+    let name_of = |id: i32| format!("{:0>96}", id);
+    // rows which must survive the next crash
+    let mut committed: Vec<i32> = Vec::new();
+    let mut next_id = 0;
+    for round in 0..num_rounds {
+        let config = FaultConfig {
+            seed: seed * num_rounds + round,
+            write_error_rate: 0.01,
+            short_read_rate: 0.01,
+            sync_error_rate: 0.05,
+            power_loss_at: Some(((seed * 7 + round * 31) % 200) as usize),
+        };
+        let mut num_checkpointed = next_id;
+        let mut num_inserted = next_id;
+        {
+            let store = FaultPageStore::new(DiskMgr::open_db(&name)?, config);
+            let faults = store.controller();
+            let res = (|| -> Res<()> {
+                let mut nanodb = NanoDb::build_on(store)?;
+                nanodb.init()?;
+                match nanodb.open_relation("item") {
+                    Ok(_) => {}
+                    Err(Error::RelationNotFound { .. }) => {
+                        let statement = CreateTableStatement {
+                            table_name: "item".to_string(),
+                            columns: vec![
+                                ColumnDef { name: "id".to_string(), data_type: DataType::Int},
+                                ColumnDef { name: "name".to_string(), data_type: DataType::Varchar(100)},
+                            ],
+                        };
+                        nanodb.execute_statement(SqlStatement::CreateTable(statement))?;
+                    }
+                    Err(e) => return Err(e),
+                }
+                for i in next_id..next_id + num_rows {
+                    let statement = InsertIntoStatement {
+                        table_name: "item".to_string(),
+                        values: vec![Value::Int(i), Value::String(name_of(i))],
+                    };
+                    // insertion failed halfway may leave the row
+                    num_inserted = i + 1;
+                    nanodb.execute_statement(SqlStatement::InsertInto(statement))?;
+                    if i % 10 == 9 {
+                        nanodb.close()?;
+                        committed.extend(num_checkpointed..num_inserted);
+                        num_checkpointed = num_inserted;
+                    }
+                }
+                Ok(())
+            })();
+            log::debug!("seed={} round={} result={:?} checkpointed={} injected={}",
+                seed, round, res, num_checkpointed, faults.get_num_injected());
+            // every failure must come from injected fault
+            match res {
+                Ok(_) | Err(Error::IoError(_)) => {}
+                Err(e) => return Err(e),
+            }
+            faults.lose_power();
+        }
+
+        let mut nanodb = NanoDb::build(&name)?;
+        nanodb.init()?;
+        let mut ids = Vec::new();
+        match nanodb.open_relation("item") {
+            Ok((file, schema)) => {
+                let mut scan = FileScan::new(Arc::new(Mutex::new(file)), schema);
+                while let Some((_, rec)) = scan.get_next()? {
+                    let id = rec.get_int_field(0).unwrap();
+                    assert_eq!(name_of(id), rec.get_varchar_field(1).unwrap());
+                    ids.push(id);
+                }
+            }
+            Err(Error::RelationNotFound { .. }) => {}
+            Err(e) => return Err(e),
+        }
+        let num_scanned = ids.len();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(num_scanned, ids.len(), "duplicated rows");
+        let lost: Vec<i32> = committed.iter().copied().filter(|id| ids.binary_search(id).is_err()).collect();
+        assert!(lost.is_empty(), "lost checkpointed rows {:?}", lost);
+        // only insertions after the last checkpoint may be written partly
+        let num_uncommitted = num_scanned - committed.len();
+        assert!(num_uncommitted <= (num_inserted - num_checkpointed) as usize);
+        assert!(ids.iter().all(|id| committed.contains(id) || (num_checkpointed..num_inserted).contains(id)));
+        // rows found now are synced by close
+        nanodb.close()?;
+        committed = ids;
+        next_id = num_inserted;
+    }
+
+    std::fs::remove_file(&name).unwrap();
+    Ok(())
+}
+
+// row larger than a page is stored in overflow pages and read back as one record
+pub fn run_nanodb_long_value() -> Res<()> {
+    let mut nanodb = NanoDb::build_in_memory()?;
+    nanodb.init()?;
+    let statement = CreateTableStatement {
+        table_name: "doc".to_string(),
+        columns: vec![
+            ColumnDef { name: "id".to_string(), data_type: DataType::Int},
+            ColumnDef { name: "body".to_string(), data_type: DataType::Varchar(3000)},
+        ]};
+    nanodb.execute_statement(SqlStatement::CreateTable(statement))?;
+    let body = |i: i32| format!("{}:", i).repeat(700 + 100 * i as usize);
+    for i in 0..3 {
+        let statement = InsertIntoStatement {
+            table_name: "doc".to_string(),
+            values: vec![Value::Int(i), Value::String(body(i))],
+        };
+        nanodb.execute_statement(SqlStatement::InsertInto(statement))?;
+    }
+
+    let statement = SelectStatement {
+        table_name: "doc".to_string(),
+        columns: vec!["body".to_string(), "id".to_string()],
+    };
+    let mut it = nanodb.execute_select(statement)?;
+    let mut count = 0;
+    while let Some(rec) = it.next_tuple()? {
+        let id = rec.get_int_field(1).unwrap();
+        let value = rec.get_varchar_field(0).unwrap();
+        println!("{}: id={} body.len={}", rec.get_rid().unwrap(), id, value.len());
+        assert_eq!(body(id), value);
+        count += 1;
+    }
+    assert_eq!(3, count);
+    drop(it);
+    nanodb.close()
+}
+
+// fill small buffer pool, then read counters and frames back through system tables
+pub fn run_nanodb_buf_stats() -> Res<()> {
+    let mut nanodb = NanoDb::build_on_with_replacer(MemPageStore::new(PAGE_BYTE), ReplacerKind::Lru, 8)?;
+    nanodb.init()?;
+    let statement = CreateTableStatement {
+        table_name: "item".to_string(),
+        columns: vec![ColumnDef { name: "id".to_string(), data_type: DataType::Int}],
+    };
+    nanodb.execute_statement(SqlStatement::CreateTable(statement))?;
+    for i in 0..1000 {
+        let statement = InsertIntoStatement {
+            table_name: "item".to_string(),
+            values: vec![Value::Int(i)],
+        };
+        nanodb.execute_statement(SqlStatement::InsertInto(statement))?;
+    }
+    let stats = nanodb.get_bufmgr_stats();
+    println!("{:?}", stats);
+    assert!(stats.hits > 0);
+    assert!(stats.misses > 0);
+    assert!(stats.evictions > 0);
+
+    let statement = SelectStatement {
+        table_name: SYSTEM_BUF_STAT.to_string(),
+        columns: vec!["hits".to_string(), "misses".to_string(), "evictions".to_string()],
+    };
+    let mut it = nanodb.execute_select(statement)?;
+    let rec = it.next_tuple()?.unwrap();
+    println!("{}: {}", SYSTEM_BUF_STAT, rec);
+    assert!(rec.get_int_field(0).unwrap() as u64 >= stats.hits);
+    assert!(rec.get_int_field(2).unwrap() as u64 >= stats.evictions);
+    assert!(it.next_tuple()?.is_none());
+    drop(it);
+
+    let statement = SelectStatement {
+        table_name: SYSTEM_BUF_FRAME.to_string(),
+        columns: vec!["frame_no".to_string(), "page_id".to_string(), "pin_count".to_string(),
+            "dirty".to_string(), "owner".to_string()],
+    };
+    let mut it = nanodb.execute_select(statement)?;
+    let mut owners = Vec::new();
+    while let Some(rec) = it.next_tuple()? {
+        println!("{}: {}", SYSTEM_BUF_FRAME, rec);
+        owners.push(rec.get_varchar_field(4).unwrap());
+    }
+    assert!(!owners.is_empty());
+    assert!(owners.len() <= 8);
+    assert!(owners.iter().any(|owner| owner == "item"));
+    drop(it);
+
+    // system tables can't be modified
+    let statement = InsertIntoStatement {
+        table_name: SYSTEM_BUF_STAT.to_string(),
+        values: vec![Value::Int(0); 5],
+    };
+    assert!(nanodb.execute_statement(SqlStatement::InsertInto(statement)).is_err());
+    assert!(nanodb.open_relation(SYSTEM_BUF_FRAME).is_err());
+    nanodb.close()
+}
+
+// import many rows at once, then insert one more row through sql statement
+pub fn run_nanodb_bulk_load() -> Res<()> {
+    let mut nanodb = NanoDb::build_in_memory()?;
+    nanodb.init()?;
+    let statement = CreateTableStatement {
+        table_name: "item".to_string(),
+        columns: vec![
+            ColumnDef { name: "id".to_string(), data_type: DataType::Int},
+            ColumnDef { name: "name".to_string(), data_type: DataType::Varchar(16)},
+        ]};
+    nanodb.execute_statement(SqlStatement::CreateTable(statement))?;
+    let n = 20000;
+    let rows = (0..n).map(|i| vec![Value::Int(i), Value::String(format!("item{}", i))]);
+    assert_eq!(n as usize, nanodb.bulk_load("item", rows)?);
+    let statement = InsertIntoStatement {
+        table_name: "item".to_string(),
+        values: vec![Value::Int(n), Value::String(format!("item{}", n))],
+    };
+    nanodb.execute_statement(SqlStatement::InsertInto(statement))?;
+
+    // row with wrong number of values fails the whole load
+    let rows = vec![vec![Value::Int(n + 1), Value::String(format!("item{}", n + 1))], vec![Value::Int(n + 2)]];
+    assert!(nanodb.bulk_load("item", rows).is_err());
+    assert!(nanodb.bulk_load("no_such_table", Vec::new()).is_err());
+
+    let statement = SelectStatement {
+        table_name: "item".to_string(),
+        columns: vec!["id".to_string(), "name".to_string()],
+    };
+    let ids = nanodb.execute_select(statement)?.tuples().map(|tuple| {
+        let tuple = tuple?;
+        let id = tuple.get_int_field(0).unwrap();
+        assert_eq!(format!("item{}", id), tuple.get_varchar_field(1).unwrap());
+        Ok(id)
+    }).collect::<Res<Vec<_>>>()?;
+    // rows are stored in the order of loading
+    assert_eq!((0..n + 1).collect::<Vec<_>>(), ids);
+    nanodb.close()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nanodb_reopen() {
+        run_nanodb_reopen().unwrap();
+    }
+
+    #[test]
+    fn test_nanodb_page_byte() {
+        run_nanodb_page_byte().unwrap();
+    }
+
+    #[test]
+    fn test_nanodb_fault_injection() {
+        for seed in 0..20 {
+            run_nanodb_fault_injection(seed).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_nanodb_long_value() {
+        run_nanodb_long_value().unwrap();
+    }
+
+    #[test]
+    fn test_nanodb_buf_stats() {
+        run_nanodb_buf_stats().unwrap();
+    }
+
+    #[test]
+    fn test_nanodb_bulk_load() {
+        run_nanodb_bulk_load().unwrap();
+    }
 }
\ No newline at end of file
@@ -1,26 +1,78 @@
-use super::types::{Res, Error};
+use super::types::{Res, Error, PageId};
 // pub const PAGE_BYTE: usize = 128; // each page has 64 bytes
-pub const PAGE_BYTE: usize = 1024;
+pub const PAGE_BYTE: usize = 1024; // default page size of new database
+pub const MIN_PAGE_BYTE: usize = 512;
+pub const MAX_PAGE_BYTE: usize = 32768;
 
+// page header
+// checksum: 4  (crc32c of page_id and data)
+// reserved: 4
+pub const PAGE_HEADER_BYTE: usize = 8;
+const PAGE_CHECKSUM: usize = 0;
+
+#[derive(Clone)]
 pub struct Page {
-    data: [u8; PAGE_BYTE],
+    data: Vec<u8>,
 }
 
 impl Page {
-    pub fn new() -> Self {
+    pub fn new(page_byte: usize) -> Self {
+        debug_assert!(page_byte > PAGE_HEADER_BYTE);
         Self {
-            data: [0; PAGE_BYTE],
+            data: vec![0; page_byte],
         }
     }
 
+    pub fn is_valid_page_byte(page_byte: usize) -> bool {
+        page_byte.is_power_of_two() && (MIN_PAGE_BYTE..=MAX_PAGE_BYTE).contains(&page_byte)
+    }
+
+    // size of whole page including header
+    pub fn get_page_byte(&self) -> usize {
+        self.data.len()
+    }
+
+    // size of data area following page header
+    pub fn get_size(&self) -> usize {
+        self.data.len() - PAGE_HEADER_BYTE
+    }
+
     pub fn get_data(&self) -> &[u8] {
-        &self.data
+        &self.data[PAGE_HEADER_BYTE..]
     }
 
     pub fn get_data_mut(&mut self) -> &mut [u8] {
+        &mut self.data[PAGE_HEADER_BYTE..]
+    }
+
+    // whole page including header. used for disk I/O.
+    pub fn get_raw_data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn get_raw_data_mut(&mut self) -> &mut [u8] {
         &mut self.data
     }
 
+    fn compute_checksum(&self, page_id: PageId) -> u32 {
+        let crc = crc32c_update(!0, &(page_id as u32).to_le_bytes());
+        !crc32c_update(crc, self.get_data())
+    }
+
+    pub fn update_checksum(&mut self, page_id: PageId) {
+        let crc = self.compute_checksum(page_id);
+        self.data[PAGE_CHECKSUM..PAGE_CHECKSUM+4].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    pub fn verify_checksum(&self, page_id: PageId) -> Res<()> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(&self.data[PAGE_CHECKSUM..PAGE_CHECKSUM+4]);
+        if u32::from_le_bytes(buf) != self.compute_checksum(page_id) {
+            return Err(Error::Corruption { page_id });
+        }
+        Ok(())
+    }
+
     // pub fn set_data(&mut self, data: &[u8]) -> Res<()> {
     //     if data.len() != PAGE_BYTE {
     //         return Err(Error::InvalidArg{ msg: format!("data length must be {}", PAGE_BYTE)});
@@ -34,51 +86,95 @@ impl Page {
     // }
 
     pub fn get_int_value(&self, offset: usize) -> Res<i32> {
-        if offset + 4 > PAGE_BYTE {
-            return Err(Error::InvalidArg{ msg: format!("offset must be less than {}", PAGE_BYTE)});
+        if offset + 4 > self.get_size() {
+            return Err(Error::InvalidArg{ msg: format!("offset must be less than {}", self.get_size())});
         }
         let mut buf = [0; 4];
-        buf.copy_from_slice(&self.data[offset..offset+4]);
+        buf.copy_from_slice(&self.get_data()[offset..offset+4]);
         Ok(i32::from_le_bytes(buf))
     }
 
     pub fn set_int_value(&mut self, offset: usize, value: i32) -> Res<()> {
-        if offset + 4 > PAGE_BYTE {
-            return Err(Error::InvalidArg{ msg: format!("offset must be less than {}", PAGE_BYTE)});
+        if offset + 4 > self.get_size() {
+            return Err(Error::InvalidArg{ msg: format!("offset must be less than {}", self.get_size())});
         }
-        self.data[offset..offset+4].copy_from_slice(&value.to_le_bytes());
+        self.get_data_mut()[offset..offset+4].copy_from_slice(&value.to_le_bytes());
         Ok(())
     }
 
     pub fn get_varchar_value(&self, offset: usize, length: usize) -> Res<String> {
-        if offset + length > PAGE_BYTE {
-            return Err(Error::InvalidArg{ msg: format!("offset must be less than {}", PAGE_BYTE)});
+        if offset + length > self.get_size() {
+            return Err(Error::InvalidArg{ msg: format!("offset must be less than {}", self.get_size())});
         }
         let mut buf = vec![0; length];
-        buf.copy_from_slice(&self.data[offset..offset+length]);
+        buf.copy_from_slice(&self.get_data()[offset..offset+length]);
         Ok(String::from_utf8(buf).unwrap())
     }
 
     pub fn set_varchar_value(&mut self, offset: usize, value: &str) -> Res<()> {
-        if offset + value.len() > PAGE_BYTE {
-            return Err(Error::InvalidArg{ msg: format!("offset must be less than {}", PAGE_BYTE)});
+        if offset + value.len() > self.get_size() {
+            return Err(Error::InvalidArg{ msg: format!("offset must be less than {}", self.get_size())});
         }
-        self.data[offset..offset+value.len()].copy_from_slice(value.as_bytes());
+        self.get_data_mut()[offset..offset+value.len()].copy_from_slice(value.as_bytes());
         Ok(())
     }
 
     pub fn get_byte_value(&self, offset: usize) -> Res<u8> {
-        if offset + 1 > PAGE_BYTE {
-            return Err(Error::InvalidArg{ msg: format!("offset must be less than {}", PAGE_BYTE)});
+        if offset + 1 > self.get_size() {
+            return Err(Error::InvalidArg{ msg: format!("offset must be less than {}", self.get_size())});
         }
-        Ok(self.data[offset])
+        Ok(self.get_data()[offset])
     }
 
     pub fn set_byte_value(&mut self, offset: usize, value: u8) -> Res<()> {
-        if offset + 1 > PAGE_BYTE {
-            return Err(Error::InvalidArg{ msg: format!("offset must be less than {}", PAGE_BYTE)});
+        if offset + 1 > self.get_size() {
+            return Err(Error::InvalidArg{ msg: format!("offset must be less than {}", self.get_size())});
         }
-        self.data[offset] = value;
+        self.get_data_mut()[offset] = value;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+const CRC32C_POLY: u32 = 0x82f6_3b78; // reversed Castagnoli polynomial
+
+const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ CRC32C_POLY } else { crc >> 1 };
+            k += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32c_update(crc: u32, data: &[u8]) -> u32 {
+    data.iter().fold(crc, |crc, b| {
+        CRC32C_TABLE[((crc ^ *b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(0xe306_9283, !crc32c_update(!0, b"123456789"));
+
+        let mut page = Page::new(PAGE_BYTE);
+        page.set_varchar_value(10, "hello").unwrap();
+        page.update_checksum(3);
+        assert!(page.verify_checksum(3).is_ok());
+        // checksum is bound to page id
+        assert!(matches!(page.verify_checksum(4), Err(Error::Corruption { page_id: 4 })));
+
+        page.set_byte_value(11, b'a').unwrap();
+        assert!(page.verify_checksum(3).is_err());
+    }
+}
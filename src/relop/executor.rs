@@ -0,0 +1,120 @@
+use crate::filemgr::RecordId;
+use crate::types::Res;
+use super::{Schema, Tuple};
+
+// operator of query plan. operator takes its children as Box<dyn Executor>
+// so that any operator can be placed under it.
+pub trait Executor {
+    fn get_schema(&self) -> &Schema;
+
+    // record id of the tuple returned by next call of next_tuple
+    fn peer_next_rid(&mut self) -> Res<Option<RecordId>>;
+
+    fn next_tuple(&mut self) -> Res<Option<Tuple>>;
+
+    // std iterator over remaining tuples. it ends after the first error.
+    fn tuples(self) -> Tuples<Self> where Self: Sized {
+        Tuples { executor: self, finished: false }
+    }
+}
+
+impl<E: Executor + ?Sized> Executor for Box<E> {
+    fn get_schema(&self) -> &Schema {
+        (**self).get_schema()
+    }
+
+    fn peer_next_rid(&mut self) -> Res<Option<RecordId>> {
+        (**self).peer_next_rid()
+    }
+
+    fn next_tuple(&mut self) -> Res<Option<Tuple>> {
+        (**self).next_tuple()
+    }
+}
+
+pub struct Tuples<E> {
+    executor: E,
+    finished: bool,
+}
+
+impl<E: Executor> Iterator for Tuples<E> {
+    type Item = Res<Tuple>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.executor.next_tuple() {
+            Ok(Some(tuple)) => Some(Ok(tuple)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(e) => {
+                self.finished = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::diskmgr::MemPageStore;
+    use crate::filemgr::HFileMgr;
+    use crate::page::PAGE_BYTE;
+    use crate::relop::{AttributeType, FileScan, FileScanOnPage, Projection};
+
+    use super::*;
+
+    #[test]
+    fn test_executor() -> Res<()> {
+        let mut filemgr = HFileMgr::build_on(MemPageStore::new(PAGE_BYTE))?;
+        let mut file = filemgr.create_file("file0")?;
+        let schema = Arc::new(Schema::build(vec![
+            ("id".to_string(), AttributeType::Int),
+            ("name".to_string(), AttributeType::Varchar(64)),
+            ("score".to_string(), AttributeType::Int),
+        ]));
+        for i in 0..40 {
+            let mut rec = Tuple::new_zero(schema.clone());
+            rec.set_int_field(0, i)?;
+            rec.set_varchar_field(1, &format!("name{}", i))?;
+            rec.set_int_field(2, i * 10)?;
+            file.insert_record(rec.get_data())?;
+        }
+        let file = Arc::new(Mutex::new(file));
+
+        // projection over projection, consumed by iterator combinators
+        let scan = FileScan::new(file.clone(), schema.clone());
+        let inner = Projection::build(Box::new(scan), vec![2, 0])?;
+        let outer = Projection::build(Box::new(inner), vec![1])?;
+        assert_eq!(1, outer.get_schema().len());
+        let ids = outer.tuples()
+            .map(|tuple| Ok(tuple?.get_int_field(0).unwrap()))
+            .filter(|id: &Res<i32>| id.as_ref().map_or(true, |id| id % 10 == 0))
+            .collect::<Res<Vec<_>>>()?;
+        assert_eq!(vec![0, 10, 20, 30], ids);
+
+        // tuples of other page are skipped
+        let mut page_ids = Vec::new();
+        file.lock().unwrap().with_record_pages(|pid, _| {
+            page_ids.push(pid);
+            Ok(())
+        })?;
+        let mut scan = FileScanOnPage::new(file.clone(), schema.clone(), page_ids[1]);
+        let rid = scan.peer_next_rid()?.unwrap();
+        let tuples = Projection::build(Box::new(scan), vec![1])?.tuples().collect::<Res<Vec<_>>>()?;
+        assert_eq!(Some(rid), tuples[0].get_rid());
+        assert!(tuples.iter().all(|tuple| tuple.get_rid().unwrap().page_id == page_ids[1]));
+        let id = FileScan::new(file.clone(), schema.clone()).tuples()
+            .find(|tuple| tuple.as_ref().map_or(true, |tuple| tuple.get_rid() == Some(rid)))
+            .unwrap()?.get_int_field(0).unwrap();
+        assert_eq!(format!("name{}", id), tuples[0].get_varchar_field(0).unwrap());
+
+        assert!(Projection::build(Box::new(FileScan::new(file, schema)), vec![3]).is_err());
+        Ok(())
+    }
+}
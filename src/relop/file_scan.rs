@@ -7,11 +7,11 @@ use crate::filemgr::{HeapFile, RawFileScan};
 
 pub struct FileScan {
     raw_file_scan: RawFileScan,
-    schema: Schema,
+    schema: Arc<Schema>,
 }
 
 impl FileScan {
-    pub fn new(heap_file: Arc<Mutex<HeapFile>>, schema: Schema) -> FileScan {
+    pub fn new(heap_file: Arc<Mutex<HeapFile>>, schema: Arc<Schema>) -> FileScan {
         let raw_file_scan = RawFileScan::new(heap_file);
         FileScan {
             raw_file_scan,
@@ -19,28 +19,25 @@ impl FileScan {
         }
     }
 
-    pub fn get_schema(&self) -> &Schema {
-        &self.schema
-    } 
-
-    pub fn get_field_len(&self) -> usize {
-        self.schema.len()
-    }
-
-    pub fn peer_next_rid(&mut self) -> Res<Option<RecordId>> {
-        self.raw_file_scan.peer_next_rid()
+    // full scan of large file
+    pub fn with_read_ahead(heap_file: Arc<Mutex<HeapFile>>, schema: Arc<Schema>, num_pages: usize) -> FileScan {
+        let raw_file_scan = RawFileScan::with_read_ahead(heap_file, num_pages);
+        FileScan {
+            raw_file_scan,
+            schema,
+        }
     }
 
-    pub fn get_next(&mut self) -> Res<Option<(RecordId, Record)>> {
+    pub fn get_next(&mut self) -> Res<Option<(RecordId, Tuple)>> {
         let res = self.raw_file_scan.get_next()?
         .map(|(rid, data)| {
-            (rid, Record::new(data, &self.schema))
+            (rid, Tuple::new(Some(rid), data, self.schema.clone()))
         });
         Ok(res)
     }
 
-    pub fn print(heap_file: Arc<Mutex<HeapFile>>, schema: Schema) -> Res<()> {
-        let mut a = FileScan::new(heap_file, schema);
+    pub fn print(heap_file: Arc<Mutex<HeapFile>>, schema: Arc<Schema>) -> Res<()> {
+        let mut a = FileScan::with_read_ahead(heap_file, schema, 4);
         while let Some((rid, rec)) = a.get_next()? {
             println!("{}: {}", rid, rec);
         }
@@ -48,6 +45,22 @@ impl FileScan {
     }
 }
 
+impl Executor for FileScan {
+    fn get_schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn peer_next_rid(&mut self) -> Res<Option<RecordId>> {
+        self.raw_file_scan.peer_next_rid()
+    }
+
+    fn next_tuple(&mut self) -> Res<Option<Tuple>> {
+        let res = self.raw_file_scan.get_next()?
+        .map(|(rid, data)| Tuple::new(Some(rid), data, self.schema.clone()));
+        Ok(res)
+    }
+}
+
 // FIXME: generalize condition by closure
 pub struct FileScanOnPage {
     base: FileScan,
@@ -55,41 +68,36 @@ pub struct FileScanOnPage {
 }
 
 impl FileScanOnPage {
-    pub fn new(heap_file: Arc<Mutex<HeapFile>>, schema: Schema, page_id: PageId) -> FileScanOnPage {
+    pub fn new(heap_file: Arc<Mutex<HeapFile>>, schema: Arc<Schema>, page_id: PageId) -> FileScanOnPage {
         let base = FileScan::new(heap_file, schema);
         FileScanOnPage {
             base,
             page_id,
         }
     }
+}
 
-    pub fn peer_next_rid(&mut self) -> Res<Option<RecordId>> {
+impl Executor for FileScanOnPage {
+    fn get_schema(&self) -> &Schema {
+        &self.base.schema
+    }
+
+    // records of other pages are skipped
+    fn peer_next_rid(&mut self) -> Res<Option<RecordId>> {
         while let Some(rid) = self.base.peer_next_rid()? {
             if rid.page_id == self.page_id {
-                break;
+                return Ok(Some(rid));
             }
             self.base.get_next()?;
         }
-        self.base.peer_next_rid()
-        // let res = match self.base.peer_next_rid()? {
-        //     None => None,
-        //     Some(rid) if rid.page_id == self.page_id => Some(rid),
-        //     Some(_rid) => {
-        //         self.get_next()
-        //     },
-        // };
-        // Ok(res)
+        Ok(None)
     }
 
-    pub fn get_next(&mut self) -> Res<Option<(RecordId, Record)>> {
-        // self.base.get_next()
-        while let Some(rid) = self.base.peer_next_rid()? {
-            if rid.page_id == self.page_id {
-                break;
-            }
-            self.base.get_next()?;
+    fn next_tuple(&mut self) -> Res<Option<Tuple>> {
+        match self.peer_next_rid()? {
+            Some(_) => self.base.next_tuple(),
+            None => Ok(None),
         }
-        self.base.get_next()
     }
 }
 
@@ -98,43 +106,50 @@ mod tests {
     use std::sync::Arc;
 
     use crate::filemgr::HFileMgr;
+    use crate::diskmgr::MemPageStore;
+    use crate::page::PAGE_BYTE;
 
     use super::*;
 
     #[test]
     fn test_merge_sort() -> Res<()> {
-        let name = "nano-file-scan.db";
-        let mut filemgr = HFileMgr::build_default(name)?;
+        let mut filemgr = HFileMgr::build_on(MemPageStore::new(PAGE_BYTE))?;
         let mut file = filemgr.create_file("file0")?;
-        let schema = Schema::build(vec![
+        let schema = Arc::new(Schema::build(vec![
             ("id".to_string(), AttributeType::Int),
-            ("name".to_string(), AttributeType::Varchar(4)),
+            ("name".to_string(), AttributeType::Varchar(64)),
             ("score".to_string(), AttributeType::Int),
-        ]);
+        ]));
 
         let scores = [1, 5, 2, 6, 7, 3, 8, 9, 2, 5, 1, 3, 9, 21, 10, 13];
-        for i in 0..scores.len() {
-            let mut rec = Record::new_zero(&schema);
+        for (i, score) in scores.iter().enumerate() {
+            let mut rec = Tuple::new_zero(schema.clone());
             rec.set_int_field(0, i as i32)?;
             rec.set_varchar_field(1, &"KVM".to_string())?;
-            rec.set_int_field(2, scores[i])?;
-            file.insert_record(*rec.get_data())?;
+            rec.set_int_field(2, *score)?;
+            file.insert_record(rec.get_data())?;
         }
 
         let file = Arc::new(Mutex::new(file));
         println!("print all");
         FileScan::print(file.clone(), schema.clone())?;
 
-        let mut scan = FileScanOnPage::new(file.clone(), schema.clone(), 3);
-        println!("print only page 3");
-        while let Some((rid, rec)) = scan.get_next()? {
-            println!("{} : {}", rid, rec);
+        let mut page_ids = Vec::new();
+        file.lock().unwrap().with_record_pages(|pid, _| {
+            page_ids.push(pid);
+            Ok(())
+        })?;
+        let second_page_id = page_ids[1];
+        let mut scan = FileScanOnPage::new(file.clone(), schema.clone(), second_page_id);
+        println!("print only page {}", second_page_id);
+        while let Some(tuple) = scan.next_tuple()? {
+            println!("{} : {}", tuple.get_rid().unwrap(), tuple);
         }
 
-        let mut scan = FileScanOnPage::new(file.clone(), schema.clone(), 3);
-        scan.get_next()?;
-        let (rid, _b) = scan.get_next()?.unwrap();
-        assert_eq!(rid.page_id, 3);
+        let mut scan = FileScanOnPage::new(file.clone(), schema.clone(), second_page_id);
+        scan.next_tuple()?;
+        let rid = scan.next_tuple()?.unwrap().get_rid().unwrap();
+        assert_eq!(rid.page_id, second_page_id);
         assert_eq!(rid.slot_no.value, 1);
         // assert_eq!(2, scan.peer_next_rid()?.unwrap().slot_no.value);
         // assert_eq!(2, scan.get_next()?.unwrap().0.slot_no.value);
@@ -143,7 +158,6 @@ mod tests {
         //     scan.get_next()?;
         // }
 
-        std::fs::remove_file(name).unwrap();
         Ok(())
     }
 
@@ -1,54 +1,44 @@
 use std::sync::{Arc, Mutex};
 
-use crate::{diskmgr::DiskMgr, bufmgr::BufMgr, filemgr::{HFileMgr, SlotNo, RecordId, RecordPage, PAGE_RECORD_BYTE}, relop::{AttributeType, Record, FileScan, FileScanOnPage}};
+use crate::{diskmgr::MemPageStore, page::{Page, PAGE_BYTE}, bufmgr::BufMgr, filemgr::{HFileMgr, SlotNo, RecordId, RecordPage}, relop::{AttributeType, Tuple, FileScan, FileScanOnPage, Executor}};
 use crate::types::*;
 use crate::filemgr::HeapFile;
 use super::Schema;
 
 pub struct Relation {
     file: Arc<Mutex<HeapFile>>,
-    schema: Schema,    
+    schema: Arc<Schema>,
 }
 
 impl Relation {
-    pub fn new(file: Arc<Mutex<HeapFile>>, schema: Schema) -> Relation {
+    pub fn new(file: Arc<Mutex<HeapFile>>, schema: Arc<Schema>) -> Relation {
         Relation {
             file,
             schema
         }
     }
 
-    pub fn get_record(&self, rid: RecordId) -> Res<Record> {
+    pub fn get_record(&self, rid: RecordId) -> Res<Tuple> {
         let file = self.file.lock().unwrap();
         let data = file.get_record(rid)?;
-        Ok(Record::new(data, &self.schema))
+        Ok(Tuple::new(Some(rid), data, self.schema.clone()))
     }
 
     pub fn arc_mutex_file(&self) -> Arc<Mutex<HeapFile>> {
         self.file.clone()
     }
 
-    pub fn get_schema(&self) -> &Schema {
-        &self.schema
-    }
-
-    pub fn truncate(&mut self) -> Res<()> {
-        log::trace!("truncate");
-        let f = self.file.lock().unwrap();
-        f.with_record_pages(|_pid, p| {
-            p.free_all()
-        })?;
-        Ok(())
+    pub fn arc_schema(&self) -> Arc<Schema> {
+        self.schema.clone()
     }
 
     // pub fn insert_record(&self, rec: Record) -> Res<()> {
     //     self.insert_bytes(*rec.get_data())
     // }
 
-    pub fn insert_bytes(&self, data: [u8; PAGE_RECORD_BYTE]) -> Res<()> {
+    pub fn update_bytes(&self, rid: RecordId, data: &[u8]) -> Res<()> {
         let mut f = self.file.lock().unwrap();
-        f.insert_record(data)?;
-        Ok(())
+        f.update_record(rid, data)
     }
 }
 
@@ -78,7 +68,7 @@ impl MergeSort {
             pids.push(pid);
             let hf = self.rel.arc_mutex_file();
             let hf = hf.lock().unwrap();
-            pid = hf.with_record_page(pid, |page| page.get_next_page_id())?;
+            pid = hf.with_record_page_read(pid, |page| page.get_next_page_id())?;
         }
 
         self.pass_0()?;
@@ -120,7 +110,7 @@ impl MergeSort {
     fn pass_1(&mut self, pid0: PageId, pid1: PageId) -> Res<()> {
         log::debug!("pass_1");
         let file = self.rel.arc_mutex_file();
-        let schema = self.rel.get_schema();
+        let schema = self.rel.arc_schema();
         // let hf = file.lock().unwrap();
         let mut scan0 = FileScanOnPage::new(file.clone(), schema.clone(), pid0);
         let mut scan1 = FileScanOnPage::new(file.clone(), schema.clone(), pid1);
@@ -132,11 +122,11 @@ impl MergeSort {
         loop {
             let rec = match (scan0.peer_next_rid()?, scan1.peer_next_rid()?) {
                 (Some(rid0), None) => {
-                    scan0.get_next()?;
+                    scan0.next_tuple()?;
                     self.rel.get_record(rid0)?
                 }
                 (None, Some(rid1)) => {
-                    scan1.get_next()?;
+                    scan1.next_tuple()?;
                     self.rel.get_record(rid1)?
                 },
                 (Some(rid0), Some(rid1)) => {
@@ -146,33 +136,33 @@ impl MergeSort {
                     let key0 = rec0.get_int_field(self.key_fno).unwrap();
                     let key1 = rec1.get_int_field(self.key_fno).unwrap();
                     if key0 < key1 {
-                        scan0.get_next()?;
+                        scan0.next_tuple()?;
                         rec0
                     } else {
-                        scan1.get_next()?;
+                        scan1.next_tuple()?;
                         rec1
                     }
                 },
                 _ => break,
             };
             let d = rec.get_data();
-            recs.push(*d);
+            recs.push(d.to_vec());
         }
 
-        log::trace!("pass_1: truncate existing data");
-        self.rel.truncate()?;
-
-        log::trace!("pass_1: insert sorted record");
-        for data in recs {
-            self.rel.insert_bytes(data)?
+        log::trace!("pass_1: overwrite records in file order");
+        let rids = FileScan::new(file.clone(), schema.clone()).tuples()
+            .map(|tuple| Ok(tuple?.get_rid().unwrap()))
+            .collect::<Res<Vec<_>>>()?;
+        for (rid, data) in rids.into_iter().zip(recs) {
+            self.rel.update_bytes(rid, &data)?
         }
 
         Ok(())
     }
 
-    fn get_key(&self, slot_no: SlotNo, page: &mut RecordPage) -> Res<i32> {
+    fn get_key(&self, slot_no: SlotNo, page: &RecordPage<&mut Page>) -> Res<i32> {
         let a = page.get_slot(slot_no)?;
-        let rec = Record::new(a, self.rel.get_schema());
+        let rec = Tuple::new(None, a.to_vec(), self.rel.arc_schema());
         let v = rec.get_int_field(self.key_fno).unwrap();
         Ok(v)
     }
@@ -180,27 +170,25 @@ impl MergeSort {
 }
 
 pub fn run_merge_sort() -> Res<()> {
-    let name = "nano-merge-sort.db";
-    let diskmgr = DiskMgr::open_db(name)?;
-    let bufmgr = BufMgr::new(10, diskmgr);
-    let bufmgr = Arc::new(Mutex::new(bufmgr));
+    let bufmgr = BufMgr::new(10, MemPageStore::new(PAGE_BYTE));
+    let bufmgr = Arc::new(bufmgr);
     let mut filemgr = HFileMgr::build(bufmgr)?;
 
-    let schema = Schema::build(vec![
+    let schema = Arc::new(Schema::build(vec![
         ("id".to_string(), AttributeType::Int),
-        ("name".to_string(), AttributeType::Varchar(4)),
+        ("name".to_string(), AttributeType::Varchar(64)),
         ("score".to_string(), AttributeType::Int),
-    ]);
+    ]));
 
     let mut file = filemgr.create_file("file0")?;
 
     let scores = [1, 5, 2, 6, 7, 3, 8, 9, 2, 5, 1, 3, 9];
-    for i in 0..scores.len() {
-        let mut rec = Record::new_zero(&schema);
+    for (i, score) in scores.iter().enumerate() {
+        let mut rec = Tuple::new_zero(schema.clone());
         rec.set_int_field(0, i as i32)?;
         rec.set_varchar_field(1, &"KVM".to_string())?;
-        rec.set_int_field(2, scores[i])?;
-        file.insert_record(*rec.get_data())?;
+        rec.set_int_field(2, *score)?;
+        file.insert_record(rec.get_data())?;
     }
 
     let file = Arc::new(Mutex::new(file));
@@ -219,8 +207,15 @@ pub fn run_merge_sort() -> Res<()> {
 
     log::info!("print after");
     FileScan::print(file.clone(), schema.clone())?;
+    let mut scan = FileScan::new(file.clone(), schema.clone());
+    let mut sorted = Vec::new();
+    while let Some((_, rec)) = scan.get_next()? {
+        sorted.push(rec.get_int_field(2).unwrap());
+    }
+    let mut expected = scores.to_vec();
+    expected.sort();
+    assert_eq!(expected, sorted);
 
-    std::fs::remove_file(name).unwrap();
     Ok(())
 }
 
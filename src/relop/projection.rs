@@ -1,53 +1,65 @@
+use std::sync::Arc;
+
 use crate::{types::*, filemgr::RecordId};
-use super::{FileScan, Record, Schema, AttributeType};
+use super::{Executor, Tuple, Schema, AttributeType};
 
 pub struct Projection {
-    // FIXME: define Iterator interface
-    iterator: FileScan,
-    schema: Schema,
+    child: Box<dyn Executor>,
+    schema: Arc<Schema>,
     fnos: Vec<usize>,
 }
 
 impl Projection {
-    pub fn build(iterator: FileScan, fnos: Vec<usize>) -> Res<Self> {
-        let schema = iterator.get_schema()
+    pub fn build(child: Box<dyn Executor>, fnos: Vec<usize>) -> Res<Self> {
+        let schema = child.get_schema()
         .projection(&fnos)
         .ok_or(Error::InvalidArg { 
             msg: format!(
                 "there exist fno which exceed field size. field.len={}, fnos={:?}", 
-                iterator.get_field_len(),
+                child.get_schema().len(),
                 fnos,
             )
         })?;
         Ok(Projection {
-            iterator,
-            schema,
+            child,
+            schema: Arc::new(schema),
             fnos,
         })
     }
+}
+
+impl Executor for Projection {
+    fn get_schema(&self) -> &Schema {
+        &self.schema
+    }
 
-    pub fn get_next(&mut self) -> Res<Option<(RecordId, Record)>> {
-        let res = self.iterator.get_next()?;
+    fn peer_next_rid(&mut self) -> Res<Option<RecordId>> {
+        self.child.peer_next_rid()
+    }
+
+    fn next_tuple(&mut self) -> Res<Option<Tuple>> {
+        let res = self.child.next_tuple()?;
         match res {
             None => Ok(None),
-            Some((rid, rec)) => {
-                let mut new_rec = Record::new_zero(&self.schema);
+            Some(tuple) => {
+                let data = vec![0; self.schema.get_record_size()];
+                let mut new_tuple = Tuple::new(tuple.get_rid(), data, self.schema.clone());
                 for new_fno in 0..self.fnos.len() {
                     let fno = self.fnos.get(new_fno).unwrap();
                     // FIXME: transfer byte directory
                     match self.schema.get_type(new_fno).unwrap() {
                         AttributeType::Int => {
-                            let v = rec.get_int_field(*fno).unwrap();
-                            new_rec.set_int_field(new_fno, v)?;
+                            let v = tuple.get_int_field(*fno).unwrap();
+                            new_tuple.set_int_field(new_fno, v)?;
                         }
                         AttributeType::Varchar(_) => {
-                            let v = rec.get_varchar_field(*fno).unwrap();
-                            new_rec.set_varchar_field(new_fno, &v)?;
+                            let v = tuple.get_varchar_field(*fno).unwrap();
+                            new_tuple.set_varchar_field(new_fno, &v)?;
                         }
                     }
                 }
-                Ok(Some((rid, new_rec)))
+                Ok(Some(new_tuple))
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -46,6 +46,11 @@ impl Schema {
     //     self.lengths.get(fno)
     // }
 
+    // bytes of record with this schema
+    pub fn get_record_size(&self) -> usize {
+        self.types.iter().map(|typ| typ.get_size()).sum()
+    }
+
     pub fn get_name(&self, fno: usize) -> Option<&String> {
         self.names.get(fno)
     }
@@ -125,10 +130,7 @@ mod tests {
         let schema = schema.projection(&fnos).unwrap();
         assert_eq!(2, schema.len());
         assert_eq!("name", schema.get_name(0).unwrap());
-        assert!(match schema.get_type(1).unwrap() {
-            AttributeType::Int => true,
-            _ => false,
-        });
+        assert!(matches!(schema.get_type(1).unwrap(), AttributeType::Int));
         println!("{:?}", schema);
     }
 }
\ No newline at end of file
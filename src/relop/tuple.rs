@@ -1,38 +1,49 @@
+use std::sync::Arc;
+
 use crate::converter::set_int_value;
+use crate::filemgr::RecordId;
 use crate::types::{Res, Error};
 
 use super::AttributeType;
 use super::schema::Schema;
-use super::super::filemgr::PAGE_RECORD_BYTE;
 use super::super::converter::get_int_value;
 
-// FIXME: rename as Tuple
-pub struct Record<'a> {
-    // FIXME: use reference
-    data: [u8; PAGE_RECORD_BYTE],
-    schema: &'a Schema,
+// row of relation. it owns its data and shares the schema with the operator,
+// so tuples returned by executors outlive the call which produced them.
+// rid is none for rows which aren't read from a file yet.
+pub struct Tuple {
+    rid: Option<RecordId>,
+    data: Vec<u8>,
+    schema: Arc<Schema>,
 }
 
-impl<'a> Record<'a> {
-    pub fn new(data: [u8; PAGE_RECORD_BYTE], schema: &Schema) -> Record {
-        Record {
+impl Tuple {
+    pub fn new(rid: Option<RecordId>, data: Vec<u8>, schema: Arc<Schema>) -> Tuple {
+        Tuple {
+            rid,
             data,
             schema,
-        }        
+        }
+    }
+
+    pub fn new_zero(schema: Arc<Schema>) -> Tuple {
+        let data = vec![0; schema.get_record_size()];
+        Self::new(None, data, schema)
+    }
+
+    // record id in the file which the tuple comes from
+    pub fn get_rid(&self) -> Option<RecordId> {
+        self.rid
     }
 
-    pub fn new_zero(schema: &Schema) -> Record {
-        let data = [0; PAGE_RECORD_BYTE];
-        Self::new(data, schema)
+    pub fn get_schema(&self) -> &Schema {
+        &self.schema
     }
+
     // pub fn get_byte(&self, pos: usize) -> Option<&u8> {
     //     self.data.get(pos)
     // }
 
-    pub fn get_field_len(&self) -> usize {
-        self.schema.len()
-    }
-
     pub fn set_int_field(&mut self, fno: usize, v: i32) -> Res<()> {
         match self.schema.get_type(fno) {
             Some(AttributeType::Int) => {
@@ -41,7 +52,7 @@ impl<'a> Record<'a> {
                 Ok(())
             }
             _ => Err(Error::InvalidArg { 
-                    msg: format!("Record::set_int_field: field is not int. fno={}", fno) })
+                    msg: format!("Tuple::set_int_field: field is not int. fno={}", fno) })
         }
     }
 
@@ -61,7 +72,7 @@ impl<'a> Record<'a> {
             Some(AttributeType::Varchar(n)) => {
                 if *n < v.len() + 1 {
                     return Err(Error::InvalidArg {
-                        msg: format!("Record::set_varchar_field: field length ({}) is not enough for given string(\"{}\")",
+                        msg: format!("Tuple::set_varchar_field: field length ({}) is not enough for given string(\"{}\")",
                                     n, v
                     )
                     })
@@ -73,7 +84,7 @@ impl<'a> Record<'a> {
                 Ok(())
             }
             _ => Err(Error::InvalidArg { 
-                    msg: format!("Record::set_varchar_field: field is not varchar. fno={}", fno) })
+                    msg: format!("Tuple::set_varchar_field: field is not varchar. fno={}", fno) })
         }
     }
 
@@ -108,12 +119,16 @@ impl<'a> Record<'a> {
         }        
     }
 
-    pub fn get_data(&self) -> &[u8; PAGE_RECORD_BYTE] {
+    pub fn get_data(&self) -> &[u8] {
         &self.data
     }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
 }
 
-impl<'a> std::fmt::Display for Record<'a> {
+impl std::fmt::Display for Tuple {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for fno in 0..self.schema.len() {
             if fno > 0 {
@@ -133,23 +148,23 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_record() {
-        let schema = Schema::build(vec![
+    fn test_tuple() {
+        let schema = Arc::new(Schema::build(vec![
             ("id".to_string(), AttributeType::Int),
             ("name".to_string(), AttributeType::Varchar(10)),
             ("qty".to_string(), AttributeType::Int),
-        ]);
-        let mut record = Record::new_zero(&schema);
+        ]));
+        let mut tuple = Tuple::new_zero(schema);
 
         let id = 5;
         let name = "MyName".to_string();
         let qty = 6;
-        record.set_int_field(0, id).unwrap();
-        record.set_varchar_field(1, &name).unwrap();
-        record.set_int_field(2, qty).unwrap();
+        tuple.set_int_field(0, id).unwrap();
+        tuple.set_varchar_field(1, &name).unwrap();
+        tuple.set_int_field(2, qty).unwrap();
 
-        assert_eq!(id, record.get_int_field(0).unwrap());
-        assert_eq!(name, record.get_varchar_field(1).unwrap());
-        assert_eq!(qty, record.get_int_field(2).unwrap());
+        assert_eq!(id, tuple.get_int_field(0).unwrap());
+        assert_eq!(name, tuple.get_varchar_field(1).unwrap());
+        assert_eq!(qty, tuple.get_int_field(2).unwrap());
     }
 }
\ No newline at end of file
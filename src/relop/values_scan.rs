@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use crate::filemgr::{RecordId, SlotNo};
+use crate::types::Res;
+use super::{Executor, Schema, Tuple};
+
+// scan over rows held in memory, for tables which aren't stored in heap files.
+// rows have no page, so record id keeps position of row with page_id 0.
+pub struct ValuesScan {
+    schema: Arc<Schema>,
+    rows: Vec<Vec<u8>>,
+    pos: usize,
+}
+
+impl ValuesScan {
+    pub fn new(schema: Arc<Schema>, rows: Vec<Vec<u8>>) -> ValuesScan {
+        ValuesScan {
+            schema,
+            rows,
+            pos: 0,
+        }
+    }
+}
+
+impl Executor for ValuesScan {
+    fn get_schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn peer_next_rid(&mut self) -> Res<Option<RecordId>> {
+        if self.pos < self.rows.len() {
+            Ok(Some(RecordId::new(0, SlotNo::new(self.pos))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn next_tuple(&mut self) -> Res<Option<Tuple>> {
+        let rid = self.peer_next_rid()?;
+        Ok(rid.map(|rid| {
+            let data = std::mem::take(&mut self.rows[self.pos]);
+            self.pos += 1;
+            Tuple::new(Some(rid), data, self.schema.clone())
+        }))
+    }
+}
@@ -42,22 +42,36 @@ pub type PageId = usize;
 //     Varchar(usize),
 // }
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+// location of heap file entry in file directory
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub struct EntryNo {
-    pub value: usize,
+    pub page_id: PageId, // directory page holding the entry
+    pub value: usize,    // index of entry in the page
 }
 impl EntryNo {
-    pub fn new(value: usize) -> Self {
-        Self { value }
+    pub fn new(page_id: PageId, value: usize) -> Self {
+        Self { page_id, value }
     }
 
+    // packed into one word. entries of a page are fewer than 2^16.
+    pub fn to_raw(self) -> usize {
+        (self.page_id << 16) | self.value
+    }
+
+    pub fn from_raw(raw: usize) -> Self {
+        Self::new(raw >> 16, raw & 0xffff)
+    }
 }
 
+#[allow(dead_code, clippy::enum_variant_names)] // fields are read through Debug
 #[derive(Debug)]
 pub enum Error {
     IoError(std::io::Error),
     InvalidArg{ msg: String},
+    InvalidFormat { msg: String },
+    Corruption { page_id: PageId },
     NoFreePage,
+    NoFreeFrame,
     PageNotFound { page_id: PageId, msg: String },
     RelationNotFound { name: String, }
     // EmptyRecord,